@@ -0,0 +1,84 @@
+//! How the spatial hash holds up against checking every pair of hitboxes
+//! as the number of things in the level grows
+
+use bevy::prelude::*;
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkId, Criterion,
+};
+use pupil::physics::{are_colliding, broadphase::SpatialHash, Hitbox};
+
+/// Blocks scattered over a level that grows with how many there are, so
+/// each one has about as many neighbours whatever the count
+fn scatter(count: usize) -> Vec<(Entity, Hitbox, Transform)> {
+    let side = (count as f32).sqrt() * 150.0;
+    // A fixed LCG, so every run lays out the same level
+    let mut seed: u32 = 12345;
+    let mut next = move || {
+        seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+        (seed >> 8) as f32 / (1 << 24) as f32 * side
+    };
+    (0..count)
+        .map(|i| {
+            let hitbox = Hitbox {
+                size: Vec2::splat(100.0),
+                ..default()
+            };
+            let transform = Transform::from_xyz(next(), next(), 0.0);
+            (Entity::from_raw(i as u32), hitbox, transform)
+        })
+        .collect()
+}
+
+fn brute_force(bodies: &[(Entity, Hitbox, Transform)]) -> usize {
+    let mut pairs = 0;
+    for (i, (_, h1, t1)) in bodies.iter().enumerate() {
+        for (_, h2, t2) in bodies[i + 1..].iter() {
+            if are_colliding(h1, t1, h2, t2) {
+                pairs += 1;
+            }
+        }
+    }
+    pairs
+}
+
+fn broadphase(bodies: &[(Entity, Hitbox, Transform)]) -> usize {
+    let mut hash = SpatialHash::default();
+    for (entity, hitbox, transform) in bodies.iter() {
+        hash.insert_hitbox(*entity, hitbox, transform);
+    }
+    let mut pairs = 0;
+    for (e1, h1, t1) in bodies.iter() {
+        for e2 in hash.query_hitbox(h1, t1) {
+            if e2 <= *e1 {
+                continue;
+            }
+            let (_, h2, t2) = &bodies[e2.index() as usize];
+            if are_colliding(h1, t1, h2, t2) {
+                pairs += 1;
+            }
+        }
+    }
+    pairs
+}
+
+fn bench_broadphase(c: &mut Criterion) {
+    let mut group = c.benchmark_group("collision pairs");
+    for count in [50, 200, 1000, 3000] {
+        let bodies = scatter(count);
+        assert_eq!(brute_force(&bodies), broadphase(&bodies));
+        group.bench_with_input(
+            BenchmarkId::new("brute force", count),
+            &bodies,
+            |b, bodies| b.iter(|| brute_force(black_box(bodies))),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("spatial hash", count),
+            &bodies,
+            |b, bodies| b.iter(|| broadphase(black_box(bodies))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_broadphase);
+criterion_main!(benches);
@@ -1,2 +1,18 @@
+use crate::physics::{
+    consts::{LAYER_ALL, LAYER_SIGHT},
+    CollisionLayers,
+};
+
 pub const MAX_X_MOVE_SPEED: f32 = 300.0;
 pub const X_ACCELERATION: f32 = 60.0;
+pub const AGENT_MASS: f32 = 1.0;
+pub const AGENT_RESTITUTION: f32 = 0.4;
+/// What eyes count as, so things without sight in their mask are
+/// see-through
+pub const EYE_LAYERS: CollisionLayers = CollisionLayers {
+    layer: LAYER_SIGHT,
+    mask: LAYER_ALL,
+};
+pub const RUNS_DIR: &str = "runs";
+pub const STATS_FILE: &str = "stats.csv";
+pub const AUTOPSY_FILE: &str = "autopsy.csv";
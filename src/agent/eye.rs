@@ -1,9 +1,12 @@
 use std::f32::consts::PI;
 
-use crate::physics::{collisions::Triangle, consts::Dir, physics_move, rotate, Hitbox};
+use crate::physics::{
+    broadphase::Broadphase, collisions::Triangle, consts::Dir,
+    physics_collide, rotate, Hitbox, Moveable,
+};
 use bevy::{prelude::*, render::texture::DEFAULT_IMAGE_HANDLE, sprite::Anchor};
 
-use super::Senses;
+use super::{consts::EYE_LAYERS, Senses};
 
 #[derive(Component)]
 pub struct Eye {
@@ -16,6 +19,8 @@ pub struct SeeBox {
     pub size: Vec2,
     pub angle: f32,
     pub invert_x: bool,
+    /// Mirrored top to bottom, for when gravity is flipped
+    pub invert_y: bool,
 }
 impl SeeBox {
     pub fn two_triangles(&self, trans: Vec2) -> (Triangle, Triangle) {
@@ -49,11 +54,17 @@ impl SeeBox {
                 },
             },
         );
-        let true_ang = if self.invert_x {
-            PI - self.angle
+        let (angle, pos) = if self.invert_y {
+            (-self.angle, Vec2::new(self.pos.x, -self.pos.y))
         } else {
-            self.angle
+            (self.angle, self.pos)
         };
+        let (true_ang, offset) = if self.invert_x {
+            (PI - angle, Vec2::new(-pos.x, pos.y))
+        } else {
+            (angle, pos)
+        };
+        let trans = trans + offset;
         (
             Triangle {
                 a: trans + rotate(pre_rotation.0.a, true_ang),
@@ -68,18 +79,46 @@ impl SeeBox {
         )
     }
 
+    /// The corners of the box around everything this seebox can see
+    pub fn bounds(&self, trans: Vec2) -> (Vec2, Vec2) {
+        let (t1, t2) = self.two_triangles(trans);
+        let points = t1.get_points().into_iter().chain(t2.get_points());
+        points.fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), p| (min.min(p), max.max(p)),
+        )
+    }
+
     pub fn to_scale(&self, scale: f32) -> SeeBox {
         SeeBox {
             pos: self.pos,
             size: self.size * scale,
             angle: self.angle,
             invert_x: self.invert_x,
+            invert_y: self.invert_y,
         }
     }
 }
 
+/// The categories of things an eye can tell apart
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum SeeableKind {
+    Block,
+    Hazard,
+}
+
 #[derive(Component)]
-pub struct Seeable;
+pub struct Seeable {
+    pub kind: SeeableKind,
+}
+
+/// What an eye sees: the kind of the closest thing, and how far along the
+/// eye it is (0.0 to 1.0)
+#[derive(Clone, Copy, Debug)]
+pub struct Sight {
+    pub kind: SeeableKind,
+    pub distance: f32,
+}
 
 #[derive(Bundle)]
 pub struct EyeBundle {
@@ -113,54 +152,76 @@ impl EyeBundle {
                 size,
                 angle,
                 invert_x: false,
+                invert_y: false,
             },
         }
     }
 }
 
-pub fn is_detected(
+/// Returns the kind of the first of the candidates found inside the seebox
+pub fn detect(
     sb: &SeeBox,
     pos: Vec2,
-    seeable: &Query<(&Hitbox, &Seeable, &Transform), (With<Seeable>, Without<Eye>)>,
-) -> bool {
+    candidates: &[Entity],
+    seeable: &Query<
+        (&Hitbox, &Seeable, &Transform),
+        (With<Seeable>, Without<Eye>),
+    >,
+) -> Option<SeeableKind> {
     let (et1, et2) = sb.two_triangles(pos.clone());
-    for (hb, _, see_t) in seeable.iter() {
-        let (st1, st2) = hb.two_triangles(see_t);
-        if et1.is_colliding_with_triangle(&st1)
-            || et1.is_colliding_with_triangle(&st2)
-            || et2.is_colliding_with_triangle(&st1)
-            || et2.is_colliding_with_triangle(&st2)
-        {
-            return true;
+    for (hb, see, see_t) in seeable.iter_many(candidates) {
+        if !hb.layers.interacts(&EYE_LAYERS) {
+            continue;
+        }
+        let seen = hb.triangles(see_t).iter().any(|st| {
+            et1.is_colliding_with_triangle(st)
+                || et2.is_colliding_with_triangle(st)
+        });
+        if seen {
+            return Some(see.kind);
         }
     }
-    false
+    None
 }
 
 /// For having eyes try to see things
 pub fn eye_see(
     mut eyes: Query<(&Eye, &mut SeeBox, &Parent, &mut Transform), With<Eye>>,
-    mut agents: Query<(&Transform, &mut Senses, &Dir), Without<Eye>>,
-    seeable: Query<(&Hitbox, &Seeable, &Transform), (With<Seeable>, Without<Eye>)>,
+    mut agents: Query<
+        (&Transform, &mut Senses, &Dir, &Moveable),
+        Without<Eye>,
+    >,
+    seeable: Query<
+        (&Hitbox, &Seeable, &Transform),
+        (With<Seeable>, Without<Eye>),
+    >,
+    broadphase: Res<Broadphase>,
 ) {
     for (e, mut sb, parent, mut eye_t) in eyes.iter_mut() {
-        let Ok((agent_trans, mut senses, dir)) = agents.get_mut(parent.get()) else {continue;};
-        // Change the display based on facing direction
-        sb.invert_x = dir == &Dir::Left;
-        let true_ang = if sb.invert_x {
-            -sb.angle
-        } else {
-            PI + sb.angle
+        let Ok((agent_trans, mut senses, dir, moveable)) =
+            agents.get_mut(parent.get())
+        else {
+            continue;
         };
-        eye_t.rotation = Quat::from_axis_angle(Vec3::new(0., 0., 1.), true_ang);
+        // Change the display based on facing direction and which way is down
+        sb.invert_x = dir == &Dir::Left;
+        sb.invert_y = moveable.gravity_dir == Dir::Up;
+        let angle = if sb.invert_y { -sb.angle } else { sb.angle };
+        let true_ang = if sb.invert_x { -angle } else { PI + angle };
+        eye_t.rotation =
+            Quat::from_axis_angle(Vec3::new(0., 0., 1.), true_ang);
         let pos = Vec2 {
             x: agent_trans.translation.x,
             y: agent_trans.translation.y,
         };
-        if !is_detected(&sb, pos, &seeable) {
+        // Every shrunk seebox fits inside the full one, so its candidates
+        // do for all of them
+        let (min, max) = sb.bounds(pos);
+        let candidates = broadphase.seeable.query(min, max);
+        let Some(mut kind) = detect(&sb, pos, &candidates, &seeable) else {
             senses.data[e.ix] = None;
             continue;
-        }
+        };
         // Perform binary search with different seeboxes to find distances
         let resolution = 8;
         let mut min = 0.0;
@@ -168,17 +229,35 @@ pub fn eye_see(
         let mut mid = 0.5;
         for _ in 0..resolution {
             let sized_sb = sb.to_scale(mid);
-            if is_detected(&sized_sb, pos, &seeable) {
+            if let Some(closer_kind) =
+                detect(&sized_sb, pos, &candidates, &seeable)
+            {
                 max = mid;
+                kind = closer_kind;
             } else {
                 min = mid;
             }
             mid = (min + max) / 2.0;
         }
-        senses.data[e.ix] = Some(mid);
+        senses.data[e.ix] = Some(Sight {
+            kind,
+            distance: mid,
+        });
+    }
+}
+
+/// Seeable things can move, so they're put back in the grid once they have
+pub fn eye_broadphase(
+    mut broadphase: ResMut<Broadphase>,
+    seeable: Query<(Entity, &Hitbox, &Transform), With<Seeable>>,
+) {
+    broadphase.seeable.clear();
+    for (entity, hb, see_t) in seeable.iter() {
+        broadphase.seeable.insert_hitbox(entity, hb, see_t);
     }
 }
 
 pub fn register_eye(app: &mut App) {
-    app.add_systems(Update, eye_see.after(physics_move));
+    app.add_systems(Update, eye_broadphase.after(physics_collide))
+        .add_systems(Update, eye_see.after(eye_broadphase));
 }
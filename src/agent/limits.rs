@@ -0,0 +1,80 @@
+//! Rules that end an episode while the agent is still alive, so that one
+//! agent idling on a block can't stall the whole roll
+
+use bevy::prelude::*;
+
+use crate::{
+    environment::{
+        level::{CurrentLevel, Level},
+        reward::reward_do_judgement,
+    },
+    physics::physics_collide,
+};
+
+use super::{Agent, DeathCause, Mortal};
+
+/// When an agent last made progress, and what that progress was measured
+/// against
+#[derive(Component, Default)]
+pub struct Stagnation {
+    best_reward: Option<f32>,
+    anchor: Option<Vec2>,
+    last_progress: f32,
+}
+
+pub fn check_episode_limits(
+    mut query: Query<(Mortal, &mut Stagnation), With<Agent>>,
+    current_level: Res<CurrentLevel>,
+    levels: Res<Assets<Level>>,
+) {
+    let Some(level) = levels.get(&current_level.handle) else {
+        return;
+    };
+    for (mut mortal, mut stagnation) in query.iter_mut() {
+        if mortal.is_dead() {
+            continue;
+        }
+        let now = mortal.tracker.time_alive;
+        // Judgement hasn't run yet this frame, so this is last frame's
+        let reward = mortal.judgeable.reward;
+        let mut cause = None;
+
+        if level.episode.time_limit.is_some_and(|limit| now >= limit) {
+            cause = Some(DeathCause::Timeout);
+        } else if let Some(rule) = &level.episode.stagnation {
+            let pos = mortal.transform.translation.truncate();
+            let improved = stagnation
+                .best_reward
+                .is_none_or(|best| reward > best + rule.min_reward_gain);
+            let moved = stagnation.anchor.is_none_or(|anchor| {
+                anchor.distance(pos) >= rule.min_distance
+            });
+            if improved {
+                stagnation.best_reward = Some(reward);
+            }
+            if moved {
+                stagnation.anchor = Some(pos);
+            }
+            if improved || moved {
+                stagnation.last_progress = now;
+            } else if now - stagnation.last_progress >= rule.seconds {
+                cause = Some(DeathCause::Stagnation);
+            }
+        }
+
+        if let Some(cause) = cause {
+            mortal.kill(cause);
+        }
+    }
+}
+
+/// Runs before judgement like every other way of dying, so an agent that
+/// times out is judged dead on the same frame, before its roll can end
+pub fn register_limits(app: &mut App) {
+    app.add_systems(
+        Update,
+        check_episode_limits
+            .after(physics_collide)
+            .before(reward_do_judgement),
+    );
+}
@@ -1,20 +1,33 @@
 pub mod brain;
 pub mod consts;
 pub mod eye;
+pub mod limits;
 pub mod roll;
+pub mod select;
+pub mod stats;
 
-use bevy::prelude::*;
+use bevy::{ecs::query::WorldQuery, prelude::*};
 use consts::*;
+use serde::{Deserialize, Serialize};
 
-use self::eye::{register_eye, EyeBundle, SeeBox};
+use self::eye::{register_eye, EyeBundle, SeeBox, SeeableKind, Sight};
+use self::limits::{register_limits, Stagnation};
 use self::roll::register_roll;
+use self::select::register_select;
 use crate::animation::{
     Animatable, AnimationManager, AnimationRoot, AnimationVal,
 };
-use crate::environment::reward::Judgeable;
-use crate::meta::consts::{WINDOW_HEIGHT, WINDOW_WIDTH};
+use crate::environment::checkpoint::Checkpoint;
+use crate::environment::collectible::Collected;
+use crate::environment::level::{CurrentLevel, Level};
+use crate::environment::reward::{
+    reward_do_judgement, Judgeable, RewardTracker,
+};
 use crate::physics::consts::Dir;
-use crate::physics::{consts::GRAVITY, Hitbox, Moveable, Velocity};
+use crate::physics::{
+    consts::GRAVITY, CollisionLayers, DropThrough, Hitbox, Moveable,
+    PrevPosition, Shape, Velocity,
+};
 
 #[derive(Component)]
 pub struct Agent;
@@ -24,7 +37,38 @@ pub struct Brain {}
 
 #[derive(Component, Debug)]
 pub struct Senses {
-    data: Vec<Option<f32>>,
+    data: Vec<Option<Sight>>,
+    /// Fraction of the level's checkpoints reached so far
+    checkpoint_progress: f32,
+}
+impl Senses {
+    /// Everything sensed, flattened into the numbers a brain is fed: per eye
+    /// how far it sees (1.0 when it sees nothing) and whether that's a
+    /// hazard, then checkpoint progress
+    pub fn inputs(&self) -> Vec<f32> {
+        self.data
+            .iter()
+            .flat_map(|sight| match sight {
+                Some(sight) => {
+                    let hazard = sight.kind == SeeableKind::Hazard;
+                    [sight.distance, if hazard { 1.0 } else { 0.0 }]
+                }
+                None => [1.0, 0.0],
+            })
+            .chain(std::iter::once(self.checkpoint_progress))
+            .collect()
+    }
+}
+
+/// The shape of an agent's hitbox
+#[derive(
+    Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default,
+)]
+pub enum AgentBody {
+    #[default]
+    Box,
+    /// Rolls off of ledges instead of catching on them
+    Circle,
 }
 
 #[derive(Clone, Hash, Eq, PartialEq, Component, Debug)]
@@ -36,25 +80,100 @@ pub enum AgentAnimState {
 }
 impl Animatable for AgentAnimState {}
 
+/// Why an agent's episode ended
+#[derive(Clone, Copy, Hash, Eq, PartialEq, Debug)]
+pub enum DeathCause {
+    OutOfBounds,
+    Hazard,
+    Timeout,
+    Stagnation,
+    GoalReached,
+}
+
+#[derive(Clone, Debug)]
+pub struct DeathRecord {
+    pub cause: DeathCause,
+    pub pos: Vec2,
+    /// Seconds into the episode
+    pub time: f32,
+    pub reward: f32,
+}
+
+#[derive(Component, Default)]
+pub struct Death {
+    pub record: Option<DeathRecord>,
+}
+
+/// Everything needed to kill an agent and record how it happened
+#[derive(WorldQuery)]
+#[world_query(mutable)]
+pub struct Mortal {
+    pub transform: &'static Transform,
+    pub judgeable: &'static Judgeable,
+    pub tracker: &'static mut RewardTracker,
+    pub death: &'static mut Death,
+    pub moveable: &'static mut Moveable,
+    pub velocity: &'static mut Velocity,
+    pub anim_val: &'static mut AnimationVal<AgentAnimState>,
+}
+impl<'w> MortalItem<'w> {
+    pub fn is_dead(&self) -> bool {
+        self.anim_val.state == AgentAnimState::Dead
+    }
+
+    /// Ends the agent's episode, freezing it where it is
+    pub fn kill(&mut self, cause: DeathCause) {
+        self.death.record = Some(DeathRecord {
+            cause,
+            pos: self.transform.translation.truncate(),
+            time: self.tracker.time_alive,
+            reward: self.judgeable.reward,
+        });
+        self.moveable.gravity_enabled = false;
+        self.anim_val.state = AgentAnimState::Dead;
+        self.velocity.x = 0.0;
+        self.velocity.y = 0.0;
+    }
+}
+
 #[derive(Bundle)]
 pub struct AgentBundle {
     _agent: Agent,
     movable: Moveable,
     judgement: Judgeable,
+    reward_tracker: RewardTracker,
+    stagnation: Stagnation,
+    collected: Collected,
+    death: Death,
     dir: Dir,
     anim_state: AnimationVal<AgentAnimState>,
     senses: Senses,
     hitbox: Hitbox,
     velocity: Velocity,
+    prev_position: PrevPosition,
+    drop_through: DropThrough,
 }
 impl AgentBundle {
-    pub fn new(size: Vec2, num_senses: usize) -> AgentBundle {
+    pub fn new(
+        size: Vec2,
+        num_senses: usize,
+        layers: CollisionLayers,
+        body: AgentBody,
+    ) -> AgentBundle {
         AgentBundle {
             _agent: Agent,
             movable: Moveable {
                 gravity_enabled: true,
+                gravity_scale: 1.0,
+                gravity_dir: Dir::Down,
+                mass: AGENT_MASS,
+                restitution: AGENT_RESTITUTION,
             },
-            judgement: Judgeable { reward: 0.0 },
+            judgement: Judgeable::default(),
+            reward_tracker: RewardTracker::default(),
+            stagnation: Stagnation::default(),
+            collected: Collected::default(),
+            death: Death::default(),
             dir: Dir::Right,
             anim_state: AnimationVal {
                 state: AgentAnimState::Idle,
@@ -63,12 +182,23 @@ impl AgentBundle {
             },
             senses: Senses {
                 data: vec![None; num_senses],
+                checkpoint_progress: 0.0,
             },
-            hitbox: Hitbox {
-                pos: Vec2 { x: 0.0, y: 0.0 },
-                size,
+            hitbox: match body {
+                AgentBody::Box => Hitbox {
+                    pos: Vec2 { x: 0.0, y: 0.0 },
+                    size,
+                    shape: Shape::Rect,
+                    layers,
+                },
+                AgentBody::Circle => Hitbox {
+                    layers,
+                    ..Hitbox::circle(size.min_element() / 2.0)
+                },
             },
             velocity: Velocity { x: 0.0, y: 0.0 },
+            prev_position: PrevPosition::default(),
+            drop_through: DropThrough::default(),
         }
     }
 }
@@ -77,12 +207,14 @@ pub fn spawn_agent(
     commands: &mut Commands,
     pos: &Vec2,
     eye_info: Vec<SeeBox>,
+    layers: CollisionLayers,
+    body: AgentBody,
     asset_server: &Res<AssetServer>,
     texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
 ) {
     let size = Vec2 { x: 64.0, y: 64.0 };
     let id = commands
-        .spawn(AgentBundle::new(size.clone(), eye_info.len()))
+        .spawn(AgentBundle::new(size.clone(), eye_info.len(), layers, body))
         .id();
     for (ix, see_box) in eye_info.into_iter().enumerate() {
         let eye_id = commands
@@ -155,13 +287,32 @@ pub fn agent_update(query: Query<(&mut Transform, &Senses), With<Agent>>) {
     }
 }
 
+pub fn agent_sense_checkpoints(
+    mut query: Query<(&RewardTracker, &mut Senses), With<Agent>>,
+    checkpoints: Query<&Checkpoint>,
+) {
+    let total = checkpoints.iter().count();
+    for (tracker, mut senses) in query.iter_mut() {
+        senses.checkpoint_progress = if total == 0 {
+            0.0
+        } else {
+            tracker.checkpoints as f32 / total as f32
+        };
+    }
+}
+
 pub fn agent_anim_update(
     mut query: Query<
-        (&Velocity, &mut Dir, &mut AnimationVal<AgentAnimState>),
+        (
+            &Velocity,
+            &Moveable,
+            &mut Dir,
+            &mut AnimationVal<AgentAnimState>,
+        ),
         With<Agent>,
     >,
 ) {
-    for (vel, mut dir, mut anim_val) in query.iter_mut() {
+    for (vel, moveable, mut dir, mut anim_val) in query.iter_mut() {
         // The agent animation state machine! Huzzah!
         if anim_val.state == AgentAnimState::Dead {
             // Ignore dead boys
@@ -191,12 +342,18 @@ pub fn agent_anim_update(
         // Left / right driven by agent state, since it also affects eyes its weird to
         // keep it embedded in the animation manager
         anim_val.invert_x = *dir == Dir::Left;
+        anim_val.invert_y = moveable.gravity_dir == Dir::Up;
     }
 }
 
 pub fn agent_move(
     mut query: Query<
-        (&mut Velocity, &AnimationVal<AgentAnimState>),
+        (
+            &mut Velocity,
+            &mut DropThrough,
+            &Moveable,
+            &AnimationVal<AgentAnimState>,
+        ),
         With<Agent>,
     >,
     input: Res<Input<KeyCode>>,
@@ -204,18 +361,24 @@ pub fn agent_move(
     if query.is_empty() {
         return;
     };
-    for (mut velocity, anim_val) in query.iter_mut() {
+    for (mut velocity, mut drop, moveable, anim_val) in query.iter_mut() {
         // Ignore dead agents
         if anim_val.state == AgentAnimState::Dead {
             continue;
         }
         // Horizontal motion
-        if input.any_pressed([KeyCode::A, KeyCode::Left]) {
-            velocity.x -= X_ACCELERATION;
+        let target = if input.any_pressed([KeyCode::A, KeyCode::Left]) {
+            Some(-MAX_X_MOVE_SPEED)
         } else if input.any_pressed([KeyCode::D, KeyCode::Right]) {
-            velocity.x += X_ACCELERATION;
+            Some(MAX_X_MOVE_SPEED)
         } else {
-            velocity.x *= 0.82;
+            None
+        };
+        // Speed up towards running speed, making up what the ground rubbed
+        // off, and leave slowing down to the ground
+        if let Some(target) = target {
+            velocity.x +=
+                (target - velocity.x).clamp(-X_ACCELERATION, X_ACCELERATION);
         }
         if velocity.x.abs() > MAX_X_MOVE_SPEED {
             velocity.x =
@@ -224,37 +387,39 @@ pub fn agent_move(
         if velocity.x.abs() < 0.1 {
             velocity.x = 0.0;
         }
-        // Vertical motion
+        // Jump away from whichever way is down, dropping any speed it had
+        // along that axis
         if input.just_pressed(KeyCode::W) {
-            velocity.y = GRAVITY / 2.0;
+            let up = -moveable.gravity_dir.vector();
+            let current = Vec2::new(velocity.x, velocity.y);
+            let jumped = current - up * current.dot(up) + up * GRAVITY / 2.0;
+            velocity.x = jumped.x;
+            velocity.y = jumped.y;
         }
+        // Fall through one-way platforms for as long as it's held
+        drop.0 = input.any_pressed([KeyCode::S, KeyCode::Down]);
     }
 }
 
 pub fn check_oob(
-    mut query: Query<
-        (
-            &Transform,
-            &mut Moveable,
-            &mut Velocity,
-            &mut AnimationVal<AgentAnimState>,
-        ),
-        With<Agent>,
-    >,
+    mut query: Query<Mortal, With<Agent>>,
+    current_level: Res<CurrentLevel>,
+    levels: Res<Assets<Level>>,
 ) {
     if query.is_empty() {
         return;
     };
-    for (transform, mut moveable, mut vel, mut anim_val) in query.iter_mut() {
-        if transform.translation.x < -WINDOW_WIDTH / 2.0
-            || WINDOW_WIDTH / 2.0 <= transform.translation.x
-            || transform.translation.y < -WINDOW_HEIGHT / 2.0
-            || WINDOW_HEIGHT / 2.0 < transform.translation.y
-        {
-            moveable.gravity_enabled = false;
-            anim_val.state = AgentAnimState::Dead;
-            vel.x = 0.0;
-            vel.y = 0.0;
+    let Some(level) = levels.get(&current_level.handle) else {
+        return;
+    };
+    for mut mortal in query.iter_mut() {
+        if mortal.is_dead() {
+            // Already dead of something else
+            continue;
+        }
+        let pos = mortal.transform.translation.truncate();
+        if !level.bounds.contains(pos) {
+            mortal.kill(DeathCause::OutOfBounds);
         }
     }
 }
@@ -263,7 +428,34 @@ pub fn register_agent(app: &mut App) {
     app.add_systems(Update, agent_update)
         .add_systems(Update, agent_move)
         .add_systems(Update, agent_anim_update)
-        .add_systems(Update, check_oob);
+        .add_systems(Update, agent_sense_checkpoints)
+        .add_systems(Update, check_oob.before(reward_do_judgement));
     register_eye(app);
+    register_limits(app);
     register_roll(app);
+    register_select(app);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn senses_inputs() {
+        let senses = Senses {
+            data: vec![
+                Some(Sight {
+                    kind: SeeableKind::Hazard,
+                    distance: 0.25,
+                }),
+                None,
+                Some(Sight {
+                    kind: SeeableKind::Block,
+                    distance: 0.5,
+                }),
+            ],
+            checkpoint_progress: 0.75,
+        };
+        assert_eq!(senses.inputs(), vec![0.25, 1.0, 1.0, 0.0, 0.5, 0.0, 0.75]);
+    }
 }
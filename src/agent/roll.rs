@@ -6,18 +6,39 @@ use bevy::prelude::*;
 
 use crate::{
     animation::AnimationVal,
+    environment::{
+        goal::goal_reach,
+        hazard::hazard_kill,
+        level::{CurrentLevel, Level},
+        reward::{reward_do_judgement, Judgeable, RewardTracker},
+    },
     meta::{LevelState, MetaState},
+    physics::CollisionLayers,
 };
 
-use super::{eye::SeeBox, spawn_agent, Agent, AgentAnimState};
+use super::{
+    check_oob,
+    eye::SeeBox,
+    limits::check_episode_limits,
+    spawn_agent,
+    stats::{Autopsy, GenerationStats, RollHistory, Summary},
+    Agent, AgentAnimState, AgentBody, Death, DeathCause,
+};
 
 #[derive(Component)]
 pub struct Roll {
     iteration: u32,
     size: u32,
     spawn_point: Vec2,
+    /// What agents of this roll are on and bump into
+    layers: CollisionLayers,
+    body: AgentBody,
 }
 impl Roll {
+    pub fn iteration(&self) -> u32 {
+        self.iteration
+    }
+
     pub fn spawn_generation(
         &mut self,
         commands: &mut Commands,
@@ -34,7 +55,10 @@ impl Roll {
                     size: Vec2 { x: 100.0, y: 10.0 },
                     angle: -3.1415926 / 4.0,
                     invert_x: false,
+                    invert_y: false,
                 }],
+                self.layers,
+                self.body,
                 asset_server,
                 texture_atlases,
             );
@@ -47,24 +71,44 @@ pub struct RollBundle {
     roll: Roll,
 }
 impl RollBundle {
-    pub fn new(size: u32, spawn_point: Vec2) -> RollBundle {
+    pub fn new(
+        size: u32,
+        spawn_point: Vec2,
+        layers: CollisionLayers,
+        body: AgentBody,
+    ) -> RollBundle {
         RollBundle {
             roll: Roll {
                 iteration: 0,
                 size,
                 spawn_point,
+                layers,
+                body,
             },
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn drive_roll(
     mut commands: Commands,
     meta: ResMut<MetaState>,
     mut roll: Query<&mut Roll>,
-    agents: Query<(&AnimationVal<AgentAnimState>, Entity), With<Agent>>,
+    agents: Query<
+        (
+            &AnimationVal<AgentAnimState>,
+            &Judgeable,
+            &RewardTracker,
+            &Death,
+            Entity,
+        ),
+        With<Agent>,
+    >,
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut history: ResMut<RollHistory>,
+    current_level: Res<CurrentLevel>,
+    levels: Res<Assets<Level>>,
 ) {
     if meta.level_state != LevelState::Testing || roll.is_empty() {
         // We only do stuff when we're testing
@@ -86,15 +130,60 @@ pub fn drive_roll(
 
     let all_dead = agents
         .iter()
-        .all(|(anim_val, _)| anim_val.state == AgentAnimState::Dead);
+        .all(|(anim_val, ..)| anim_val.state == AgentAnimState::Dead);
 
     if !all_dead {
         // We don't have any work to do
         return;
     }
 
+    // Record how the finished generation did
+    if let Some(level) = levels.get(&current_level.handle) {
+        let fitnesses: Vec<f32> = agents
+            .iter()
+            .map(|(_, judgeable, ..)| judgeable.fitness(level.fitness_mode))
+            .collect();
+        let survivals: Vec<f32> = agents
+            .iter()
+            .map(|(_, _, tracker, ..)| tracker.time_alive)
+            .collect();
+        let records: Vec<_> = agents
+            .iter()
+            .filter_map(|(.., death, _)| death.record.as_ref())
+            .collect();
+        let successes = records
+            .iter()
+            .filter(|record| record.cause == DeathCause::GoalReached)
+            .count();
+        let stats = GenerationStats {
+            iteration: roll.iteration,
+            fitness: Summary::new(&fitnesses),
+            survival: Summary::new(&survivals),
+            success_rate: successes as f32 / fitnesses.len().max(1) as f32,
+        };
+        println!(
+            "Generation {}: best {:.2}, mean {:.2}, worst {:.2}",
+            stats.iteration,
+            stats.fitness.max,
+            stats.fitness.mean,
+            stats.fitness.min
+        );
+        if !level.goals.is_empty()
+            && history.solved_at.is_none()
+            && stats.success_rate >= level.solve_fraction
+        {
+            println!("Level solved in generation {}!", stats.iteration);
+            history.solved_at = Some(stats.iteration);
+        }
+        history.push(stats);
+
+        let autopsy = Autopsy::new(roll.iteration, &records);
+        println!("{}", autopsy);
+        history.push_autopsy(autopsy);
+    }
+
     // Spawn in new agents and then delete the old
-    let old_ids = agents.iter().map(|(_, id)| id);
+    let old_ids = agents.iter().map(|(.., id)| id);
     roll.spawn_generation(&mut commands, &asset_server, &mut texture_atlases);
     println!("Roll Iteration: {}", roll.iteration);
     for id in old_ids {
@@ -102,6 +191,16 @@ pub fn drive_roll(
     }
 }
 
+/// Runs after everything that can kill an agent or change its reward, so a
+/// finished generation is recorded as of the frame the last agent died
 pub fn register_roll(app: &mut App) {
-    app.add_systems(Update, drive_roll);
+    app.init_resource::<RollHistory>().add_systems(
+        Update,
+        drive_roll
+            .after(check_oob)
+            .after(check_episode_limits)
+            .after(hazard_kill)
+            .after(goal_reach)
+            .after(reward_do_judgement),
+    );
 }
@@ -0,0 +1,84 @@
+//! The agent being watched closely while testing. Things that differ per
+//! agent (like which coins are left) are shown from its point of view.
+
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::{
+    animation::AnimationVal,
+    meta::{cursor_world_pos, LevelState, MetaState},
+    physics::{get_bounds, Hitbox},
+};
+
+use super::{Agent, AgentAnimState};
+
+#[derive(Resource, Default)]
+pub struct SelectedAgent {
+    pub entity: Option<Entity>,
+}
+
+/// Clicking an agent selects it. Otherwise we stick with the current one,
+/// falling back to any agent that's still alive.
+pub fn select_agent(
+    meta: Res<MetaState>,
+    mut selected: ResMut<SelectedAgent>,
+    mouse: Res<Input<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    agents: Query<
+        (Entity, &Transform, &Hitbox, &AnimationVal<AgentAnimState>),
+        With<Agent>,
+    >,
+) {
+    if meta.level_state != LevelState::Testing {
+        selected.entity = None;
+        return;
+    }
+    if selected.entity.is_some_and(|e| agents.get(e).is_err()) {
+        // Its generation is over
+        selected.entity = None;
+    }
+    if mouse.just_pressed(MouseButton::Left) {
+        if let Some(cursor) = cursor_world_pos(&windows, &cameras) {
+            let clicked = agents.iter().find(|(_, t, hb, _)| {
+                let (left, right, top, bot) = get_bounds(hb, t);
+                left <= cursor.x
+                    && cursor.x <= right
+                    && bot <= cursor.y
+                    && cursor.y <= top
+            });
+            if let Some((entity, ..)) = clicked {
+                selected.entity = Some(entity);
+            }
+        }
+    }
+    if selected.entity.is_none() {
+        selected.entity = agents
+            .iter()
+            .find(|(.., anim_val)| anim_val.state != AgentAnimState::Dead)
+            .map(|(entity, ..)| entity);
+    }
+}
+
+pub fn draw_selected_agent(
+    selected: Res<SelectedAgent>,
+    agents: Query<(&Transform, &Hitbox), With<Agent>>,
+    mut gizmos: Gizmos,
+) {
+    let Some((t, hb)) = selected.entity.and_then(|e| agents.get(e).ok())
+    else {
+        return;
+    };
+    let (left, right, top, bot) = get_bounds(hb, t);
+    gizmos.rect_2d(
+        Vec2::new(left + right, top + bot) / 2.0,
+        0.0,
+        Vec2::new(right - left, top - bot),
+        Color::ORANGE,
+    );
+}
+
+pub fn register_select(app: &mut App) {
+    app.init_resource::<SelectedAgent>()
+        .add_systems(Update, select_agent)
+        .add_systems(Update, draw_selected_agent.after(select_agent));
+}
@@ -0,0 +1,266 @@
+//! Statistics about each generation of a roll, kept around so we can see
+//! whether the pupils are actually learning anything
+
+use std::{
+    cmp::Reverse,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::environment::consts::BLOCK_SIZE;
+
+use super::{
+    consts::{AUTOPSY_FILE, RUNS_DIR, STATS_FILE},
+    DeathCause, DeathRecord,
+};
+
+/// The usual numbers describing a bunch of values
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Summary {
+    pub max: f32,
+    pub mean: f32,
+    pub median: f32,
+    pub min: f32,
+    pub std_dev: f32,
+}
+impl Summary {
+    pub fn new(values: &[f32]) -> Summary {
+        if values.is_empty() {
+            return Summary::default();
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let n = sorted.len();
+        let mean = sorted.iter().sum::<f32>() / n as f32;
+        let median = if n.is_multiple_of(2) {
+            (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0
+        } else {
+            sorted[n / 2]
+        };
+        let variance =
+            sorted.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n as f32;
+        Summary {
+            max: sorted[n - 1],
+            mean,
+            median,
+            min: sorted[0],
+            std_dev: variance.sqrt(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GenerationStats {
+    pub iteration: u32,
+    pub fitness: Summary,
+    /// Seconds each agent stayed alive
+    pub survival: Summary,
+    /// Fraction of the generation that reached a goal
+    pub success_rate: f32,
+}
+impl GenerationStats {
+    pub const CSV_HEADER: &'static str = "generation,best,mean,median,worst,\
+        std_dev,survival_min,survival_mean,survival_median,survival_max,\
+        survival_std_dev,success_rate";
+
+    pub fn to_csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.iteration,
+            self.fitness.max,
+            self.fitness.mean,
+            self.fitness.median,
+            self.fitness.min,
+            self.fitness.std_dev,
+            self.survival.min,
+            self.survival.mean,
+            self.survival.median,
+            self.survival.max,
+            self.survival.std_dev,
+            self.success_rate,
+        )
+    }
+}
+
+/// How one cause of death played out in a generation
+#[derive(Clone, Debug)]
+pub struct CauseReport {
+    pub cause: DeathCause,
+    pub count: usize,
+    pub fraction: f32,
+    pub mean_time: f32,
+    pub mean_reward: f32,
+    /// Center of the grid cell where this cause struck most often
+    pub hotspot: Vec2,
+    pub hotspot_count: usize,
+}
+
+/// What killed a generation, and where
+#[derive(Clone, Debug)]
+pub struct Autopsy {
+    pub iteration: u32,
+    /// Most common cause first
+    pub causes: Vec<CauseReport>,
+}
+impl Autopsy {
+    pub const CSV_HEADER: &'static str = "generation,cause,count,fraction,\
+        mean_time,mean_reward,hotspot_x,hotspot_y,hotspot_count";
+
+    pub fn new(iteration: u32, records: &[&DeathRecord]) -> Autopsy {
+        let mut by_cause: HashMap<DeathCause, Vec<&DeathRecord>> =
+            HashMap::new();
+        for &record in records.iter() {
+            by_cause.entry(record.cause).or_default().push(record);
+        }
+        let total = records.len().max(1) as f32;
+        let mut causes: Vec<CauseReport> = by_cause
+            .into_iter()
+            .map(|(cause, cause_records)| {
+                let count = cause_records.len();
+                let mut cells: HashMap<(i32, i32), usize> = HashMap::new();
+                for record in cause_records.iter() {
+                    let cell = (record.pos / BLOCK_SIZE).floor();
+                    *cells
+                        .entry((cell.x as i32, cell.y as i32))
+                        .or_default() += 1;
+                }
+                let ((cell_x, cell_y), hotspot_count) = cells
+                    .into_iter()
+                    .max_by_key(|(_, count)| *count)
+                    .unwrap_or(((0, 0), 0));
+                CauseReport {
+                    cause,
+                    count,
+                    fraction: count as f32 / total,
+                    mean_time: cause_records
+                        .iter()
+                        .map(|r| r.time)
+                        .sum::<f32>()
+                        / count as f32,
+                    mean_reward: cause_records
+                        .iter()
+                        .map(|r| r.reward)
+                        .sum::<f32>()
+                        / count as f32,
+                    hotspot: (Vec2::new(cell_x as f32, cell_y as f32) + 0.5)
+                        * BLOCK_SIZE,
+                    hotspot_count,
+                }
+            })
+            .collect();
+        causes.sort_by_key(|cause| Reverse(cause.count));
+        Autopsy { iteration, causes }
+    }
+
+    pub fn to_csv_rows(&self) -> Vec<String> {
+        self.causes
+            .iter()
+            .map(|report| {
+                format!(
+                    "{},{:?},{},{},{},{},{},{},{}",
+                    self.iteration,
+                    report.cause,
+                    report.count,
+                    report.fraction,
+                    report.mean_time,
+                    report.mean_reward,
+                    report.hotspot.x,
+                    report.hotspot.y,
+                    report.hotspot_count,
+                )
+            })
+            .collect()
+    }
+}
+impl fmt::Display for Autopsy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Autopsy for generation {}:", self.iteration)?;
+        for report in self.causes.iter() {
+            write!(
+                f,
+                "\n  {:?}: {:.0}% ({} agents, {} of them around ({}, {}))",
+                report.cause,
+                report.fraction * 100.0,
+                report.count,
+                report.hotspot_count,
+                report.hotspot.x,
+                report.hotspot.y,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn append_lines(path: &Path, lines: &[String]) {
+    let write_all = |mut file: File| {
+        lines.iter().try_for_each(|line| writeln!(file, "{}", line))
+    };
+    let appended = OpenOptions::new()
+        .append(true)
+        .open(path)
+        .and_then(write_all);
+    if let Err(e) = appended {
+        println!("Couldn't write to {}: {}", path.display(), e);
+    }
+}
+
+/// Stats for every finished generation of the current run
+#[derive(Resource, Default)]
+pub struct RollHistory {
+    pub generations: Vec<GenerationStats>,
+    pub autopsies: Vec<Autopsy>,
+    /// The first generation where enough agents reached a goal
+    pub solved_at: Option<u32>,
+    /// Where stats get appended, if we managed to make one
+    run_dir: Option<PathBuf>,
+}
+impl RollHistory {
+    /// Starts a fresh history with its own directory under `RUNS_DIR`
+    pub fn new_run() -> RollHistory {
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let dir = PathBuf::from(RUNS_DIR).join(stamp.to_string());
+        let created = fs::create_dir_all(&dir)
+            .and_then(|_| {
+                fs::write(
+                    dir.join(STATS_FILE),
+                    format!("{}\n", GenerationStats::CSV_HEADER),
+                )
+            })
+            .and_then(|_| {
+                fs::write(
+                    dir.join(AUTOPSY_FILE),
+                    format!("{}\n", Autopsy::CSV_HEADER),
+                )
+            });
+        if let Err(e) = &created {
+            println!("Couldn't create run directory {}: {}", dir.display(), e);
+        }
+        RollHistory {
+            generations: vec![],
+            autopsies: vec![],
+            solved_at: None,
+            run_dir: created.ok().map(|_| dir),
+        }
+    }
+
+    pub fn push(&mut self, stats: GenerationStats) {
+        if let Some(dir) = &self.run_dir {
+            append_lines(&dir.join(STATS_FILE), &[stats.to_csv_row()]);
+        }
+        self.generations.push(stats);
+    }
+
+    pub fn push_autopsy(&mut self, autopsy: Autopsy) {
+        if let Some(dir) = &self.run_dir {
+            append_lines(&dir.join(AUTOPSY_FILE), &autopsy.to_csv_rows());
+        }
+        self.autopsies.push(autopsy);
+    }
+}
@@ -0,0 +1,7 @@
+use bevy::render::color::Color;
+
+pub const REWARD_PICK_RADIUS: f32 = 10.0;
+pub const SPAWN_PICK_RADIUS: f32 = 32.0;
+pub const MAX_HISTORY: usize = 100;
+pub const GRID_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.1);
+pub const SELECT_COLOR: Color = Color::ORANGE;
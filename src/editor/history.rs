@@ -0,0 +1,42 @@
+use crate::environment::level::Level;
+
+use super::consts::MAX_HISTORY;
+
+/// Snapshots of the level, for undo/redo
+#[derive(Default)]
+pub struct EditHistory {
+    undo: Vec<Level>,
+    redo: Vec<Level>,
+}
+impl EditHistory {
+    /// Call right before changing the level
+    pub fn record(&mut self, level: &Level) {
+        self.undo.push(level.clone());
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo(&mut self, level: &mut Level) {
+        let Some(prev) = self.undo.pop() else {
+            return;
+        };
+        self.redo.push(std::mem::replace(level, prev));
+    }
+
+    pub fn redo(&mut self, level: &mut Level) {
+        let Some(next) = self.redo.pop() else {
+            return;
+        };
+        self.undo.push(std::mem::replace(level, next));
+    }
+}
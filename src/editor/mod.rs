@@ -0,0 +1,455 @@
+//! The level editor, active while designing.
+//! Edits are made directly to the current `Level` asset (which respawns the
+//! level entities), so what gets tested is exactly what gets saved.
+
+pub mod consts;
+pub mod history;
+
+use bevy::{asset::FileAssetIo, prelude::*, window::PrimaryWindow};
+use ron::ser::PrettyConfig;
+
+use crate::{
+    environment::{
+        consts::BLOCK_SIZE,
+        level::{BlockInfo, CurrentLevel, Level, RewardInfo},
+    },
+    meta::{cursor_world_pos, LevelState, MetaState},
+    physics::rotate,
+};
+
+use self::{
+    consts::{
+        GRID_COLOR, REWARD_PICK_RADIUS, SELECT_COLOR, SPAWN_PICK_RADIUS,
+    },
+    history::EditHistory,
+};
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum EditorTool {
+    /// Create, select, move and resize blocks
+    Block,
+    SpawnPoint,
+    Reward,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Selection {
+    Block(usize),
+    Reward(usize),
+    SpawnPoint,
+}
+
+enum DragKind {
+    Create,
+    Move,
+    /// Anchor is the center of the cell in the corner that stays put
+    Resize {
+        anchor: Vec2,
+    },
+}
+
+struct Drag {
+    start: Vec2,
+    kind: DragKind,
+}
+
+pub enum Edit {
+    AddBlock(BlockInfo),
+    SetBlock(usize, BlockInfo),
+    AddReward(RewardInfo),
+    SetSpawnPoint(Vec2),
+    Move(Selection, Vec2),
+    Delete(Selection),
+}
+
+#[derive(Resource)]
+pub struct EditorState {
+    pub tool: EditorTool,
+    pub selection: Option<Selection>,
+    drag: Option<Drag>,
+    history: EditHistory,
+}
+impl EditorState {
+    /// Applies an edit to the level, recording it so it can be undone
+    pub fn apply(&mut self, level: &mut Level, edit: Edit) {
+        if let Edit::Delete(Selection::SpawnPoint) = edit {
+            // There must always be somewhere to spawn
+            return;
+        }
+        self.history.record(level);
+        self.selection = match edit {
+            Edit::AddBlock(block) => {
+                level.blocks.push(block);
+                Some(Selection::Block(level.blocks.len() - 1))
+            }
+            Edit::SetBlock(ix, block) => {
+                level.blocks[ix] = block;
+                Some(Selection::Block(ix))
+            }
+            Edit::AddReward(reward) => {
+                level.rewards.push(reward);
+                Some(Selection::Reward(level.rewards.len() - 1))
+            }
+            Edit::SetSpawnPoint(pos) => {
+                level.spawn_point = pos;
+                Some(Selection::SpawnPoint)
+            }
+            Edit::Move(selection, delta) => {
+                match selection {
+                    Selection::Block(ix) => level.blocks[ix].pos += delta,
+                    Selection::Reward(ix) => match &mut level.rewards[ix] {
+                        RewardInfo::Distance { pos } => *pos += delta,
+                    },
+                    Selection::SpawnPoint => level.spawn_point += delta,
+                }
+                Some(selection)
+            }
+            Edit::Delete(selection) => {
+                match selection {
+                    Selection::Block(ix) => {
+                        level.blocks.remove(ix);
+                    }
+                    Selection::Reward(ix) => {
+                        level.rewards.remove(ix);
+                    }
+                    Selection::SpawnPoint => unreachable!(),
+                }
+                None
+            }
+        };
+    }
+}
+
+pub fn editor_setup(mut commands: Commands) {
+    commands.insert_resource(EditorState {
+        tool: EditorTool::Block,
+        selection: None,
+        drag: None,
+        history: EditHistory::default(),
+    });
+}
+
+/// Snaps a point to the center of the grid cell it's in
+pub fn snap_to_cell(point: Vec2) -> Vec2 {
+    ((point / BLOCK_SIZE).floor() + 0.5) * BLOCK_SIZE
+}
+
+/// Snaps a movement to a whole number of grid cells
+pub fn snap_delta(delta: Vec2) -> Vec2 {
+    (delta / BLOCK_SIZE).round() * BLOCK_SIZE
+}
+
+/// The smallest grid-aligned rectangle covering the cells of both points.
+/// Returns (center, size), like a `BlockInfo`.
+pub fn cell_rect(a: Vec2, b: Vec2) -> (Vec2, Vec2) {
+    let lo = (a.min(b) / BLOCK_SIZE).floor() * BLOCK_SIZE;
+    let hi = ((a.max(b) / BLOCK_SIZE).floor() + 1.0) * BLOCK_SIZE;
+    ((lo + hi) / 2.0, hi - lo)
+}
+
+fn reward_pos(reward: &RewardInfo) -> Vec2 {
+    match reward {
+        RewardInfo::Distance { pos } => *pos,
+    }
+}
+
+/// Finds the thing under the cursor. Small things win over blocks, and later
+/// blocks win over earlier ones since they're drawn on top.
+pub fn pick(level: &Level, point: Vec2) -> Option<Selection> {
+    for (ix, reward) in level.rewards.iter().enumerate().rev() {
+        if reward_pos(reward).distance(point) <= REWARD_PICK_RADIUS {
+            return Some(Selection::Reward(ix));
+        }
+    }
+    if level.spawn_point.distance(point) <= SPAWN_PICK_RADIUS {
+        return Some(Selection::SpawnPoint);
+    }
+    for (ix, block) in level.blocks.iter().enumerate().rev() {
+        let diff = rotate(point - block.pos, -block.rotation).abs();
+        if diff.x <= block.size.x / 2.0 && diff.y <= block.size.y / 2.0 {
+            return Some(Selection::Block(ix));
+        }
+    }
+    None
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn editor_handle_mouse(
+    meta: Res<MetaState>,
+    mut editor: ResMut<EditorState>,
+    mouse: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    current_level: Res<CurrentLevel>,
+    mut levels: ResMut<Assets<Level>>,
+) {
+    if meta.level_state != LevelState::Designing {
+        editor.drag = None;
+        return;
+    }
+    let Some(cursor) = cursor_world_pos(&windows, &cameras) else {
+        return;
+    };
+    // Only borrow the level mutably when actually editing, since that
+    // respawns everything
+    let Some(level) = levels.get(&current_level.handle) else {
+        return;
+    };
+
+    let mut edit = None;
+    if mouse.just_pressed(MouseButton::Left) {
+        match editor.tool {
+            EditorTool::Block => {
+                let picked = pick(level, cursor);
+                let resizing = keys
+                    .any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+                let kind = match picked {
+                    None => DragKind::Create,
+                    Some(Selection::Block(ix)) if resizing => {
+                        let block = &level.blocks[ix];
+                        let toward_cursor = (cursor - block.pos).signum();
+                        let inset = (block.size - BLOCK_SIZE) / 2.0;
+                        DragKind::Resize {
+                            anchor: block.pos - toward_cursor * inset,
+                        }
+                    }
+                    Some(_) => DragKind::Move,
+                };
+                editor.selection = picked;
+                editor.drag = Some(Drag {
+                    start: cursor,
+                    kind,
+                });
+            }
+            EditorTool::SpawnPoint => {
+                edit = Some(Edit::SetSpawnPoint(snap_to_cell(cursor)));
+            }
+            EditorTool::Reward => {
+                edit = Some(Edit::AddReward(RewardInfo::Distance {
+                    pos: snap_to_cell(cursor),
+                }));
+            }
+        }
+    }
+    if mouse.just_released(MouseButton::Left) {
+        if let Some(drag) = editor.drag.take() {
+            edit = match (drag.kind, editor.selection) {
+                (DragKind::Create, _) => {
+                    let (pos, size) = cell_rect(drag.start, cursor);
+                    Some(Edit::AddBlock(BlockInfo {
+                        pos,
+                        size,
+                        ..default()
+                    }))
+                }
+                (DragKind::Move, Some(selection)) => {
+                    let delta = snap_delta(cursor - drag.start);
+                    if delta == Vec2::ZERO {
+                        None
+                    } else {
+                        Some(Edit::Move(selection, delta))
+                    }
+                }
+                (DragKind::Resize { anchor }, Some(Selection::Block(ix))) => {
+                    let (pos, size) = cell_rect(anchor, cursor);
+                    Some(Edit::SetBlock(
+                        ix,
+                        BlockInfo {
+                            pos,
+                            size,
+                            ..level.blocks[ix].clone()
+                        },
+                    ))
+                }
+                _ => None,
+            };
+        }
+    }
+
+    let Some(edit) = edit else {
+        return;
+    };
+    if let Some(level) = levels.get_mut(&current_level.handle) {
+        editor.apply(level, edit);
+    }
+}
+
+fn save_level(level: &Level, path: &std::path::Path) {
+    let full_path = FileAssetIo::get_base_path().join("assets").join(path);
+    let result = ron::ser::to_string_pretty(level, PrettyConfig::default())
+        .map_err(|e| e.to_string())
+        .and_then(|s| {
+            std::fs::write(&full_path, s).map_err(|e| e.to_string())
+        });
+    match result {
+        Ok(()) => println!("Saved level to {}", full_path.display()),
+        Err(e) => println!("Failed to save level: {}", e),
+    }
+}
+
+pub fn editor_handle_keys(
+    meta: Res<MetaState>,
+    mut editor: ResMut<EditorState>,
+    keys: Res<Input<KeyCode>>,
+    current_level: Res<CurrentLevel>,
+    mut levels: ResMut<Assets<Level>>,
+    asset_server: Res<AssetServer>,
+) {
+    if meta.level_state != LevelState::Designing {
+        return;
+    }
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    if keys.just_pressed(KeyCode::Key1) {
+        editor.tool = EditorTool::Block;
+    } else if keys.just_pressed(KeyCode::Key2) {
+        editor.tool = EditorTool::SpawnPoint;
+    } else if keys.just_pressed(KeyCode::Key3) {
+        editor.tool = EditorTool::Reward;
+    }
+    if keys.just_pressed(KeyCode::Escape) {
+        editor.selection = None;
+    }
+
+    if ctrl && keys.just_pressed(KeyCode::S) {
+        if let (Some(level), Some(path)) = (
+            levels.get(&current_level.handle),
+            asset_server.get_handle_path(&current_level.handle),
+        ) {
+            save_level(level, path.path());
+        }
+        return;
+    }
+
+    let undo = ctrl && !shift && keys.just_pressed(KeyCode::Z);
+    let redo = ctrl
+        && (keys.just_pressed(KeyCode::Y)
+            || (shift && keys.just_pressed(KeyCode::Z)));
+    let delete = keys.any_just_pressed([KeyCode::Delete, KeyCode::Back]);
+    let deletable = editor
+        .selection
+        .is_some_and(|selection| selection != Selection::SpawnPoint);
+    if (undo && !editor.history.can_undo())
+        || (redo && !editor.history.can_redo())
+        || (delete && !deletable)
+        || !(undo || redo || delete)
+    {
+        // Nothing to do, so don't touch (and respawn) the level
+        return;
+    }
+    let Some(level) = levels.get_mut(&current_level.handle) else {
+        return;
+    };
+    if undo {
+        editor.history.undo(level);
+        // Indices may not point at the same things anymore
+        editor.selection = None;
+    } else if redo {
+        editor.history.redo(level);
+        editor.selection = None;
+    } else if let Some(selection) = editor.selection {
+        editor.apply(level, Edit::Delete(selection));
+    }
+}
+
+fn draw_selection(
+    gizmos: &mut Gizmos,
+    level: &Level,
+    selection: Selection,
+    offset: Vec2,
+) {
+    match selection {
+        Selection::Block(ix) => {
+            let block = &level.blocks[ix];
+            gizmos.rect_2d(
+                block.pos + offset,
+                block.rotation,
+                block.size,
+                SELECT_COLOR,
+            );
+        }
+        Selection::Reward(ix) => {
+            gizmos.circle_2d(
+                reward_pos(&level.rewards[ix]) + offset,
+                REWARD_PICK_RADIUS,
+                SELECT_COLOR,
+            );
+        }
+        Selection::SpawnPoint => {
+            gizmos.circle_2d(
+                level.spawn_point + offset,
+                SPAWN_PICK_RADIUS,
+                SELECT_COLOR,
+            );
+        }
+    }
+}
+
+/// Draws the editor overlay: grid, bounds, spawn point, selection and
+/// whatever is being dragged
+pub fn editor_draw(
+    meta: Res<MetaState>,
+    editor: Res<EditorState>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    current_level: Res<CurrentLevel>,
+    levels: Res<Assets<Level>>,
+    mut gizmos: Gizmos,
+) {
+    if meta.level_state != LevelState::Designing {
+        return;
+    }
+    let Some(level) = levels.get(&current_level.handle) else {
+        return;
+    };
+
+    // Grid over the level bounds
+    let lo = level.bounds.pos - level.bounds.size / 2.0;
+    let hi = level.bounds.pos + level.bounds.size / 2.0;
+    let mut x = (lo.x / BLOCK_SIZE).ceil() * BLOCK_SIZE;
+    while x <= hi.x {
+        gizmos.line_2d(Vec2::new(x, lo.y), Vec2::new(x, hi.y), GRID_COLOR);
+        x += BLOCK_SIZE;
+    }
+    let mut y = (lo.y / BLOCK_SIZE).ceil() * BLOCK_SIZE;
+    while y <= hi.y {
+        gizmos.line_2d(Vec2::new(lo.x, y), Vec2::new(hi.x, y), GRID_COLOR);
+        y += BLOCK_SIZE;
+    }
+    gizmos.rect_2d(level.bounds.pos, 0.0, level.bounds.size, Color::RED);
+
+    gizmos.circle_2d(level.spawn_point, SPAWN_PICK_RADIUS, Color::BLUE);
+
+    let cursor = cursor_world_pos(&windows, &cameras);
+    match (&editor.drag, editor.selection, cursor) {
+        (Some(drag), _, Some(cursor)) => match drag.kind {
+            DragKind::Create => {
+                let (pos, size) = cell_rect(drag.start, cursor);
+                gizmos.rect_2d(pos, 0.0, size, SELECT_COLOR);
+            }
+            DragKind::Move => {
+                if let Some(selection) = editor.selection {
+                    let delta = snap_delta(cursor - drag.start);
+                    draw_selection(&mut gizmos, level, selection, delta);
+                }
+            }
+            DragKind::Resize { anchor } => {
+                let (pos, size) = cell_rect(anchor, cursor);
+                gizmos.rect_2d(pos, 0.0, size, SELECT_COLOR);
+            }
+        },
+        (_, Some(selection), _) => {
+            draw_selection(&mut gizmos, level, selection, Vec2::ZERO)
+        }
+        _ => (),
+    }
+}
+
+pub fn register_editor(app: &mut App) {
+    app.add_systems(Startup, editor_setup)
+        .add_systems(Update, editor_handle_mouse)
+        .add_systems(Update, editor_handle_keys)
+        .add_systems(Update, editor_draw);
+}
@@ -1,41 +1,178 @@
-use bevy::{prelude::*, render::texture::DEFAULT_IMAGE_HANDLE};
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::Indices, render_resource::PrimitiveTopology,
+        texture::DEFAULT_IMAGE_HANDLE,
+    },
+    sprite::MaterialMesh2dBundle,
+};
 
-use crate::{agent::eye::Seeable, physics::Hitbox};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    agent::eye::{Seeable, SeeableKind},
+    physics::{CollisionLayers, Hitbox, PhysicsMaterial, Shape},
+};
 
 #[derive(Component)]
 pub struct Block;
 
+/// What a block is made of, which changes how things bounce and slide on it
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum Surface {
+    #[default]
+    Normal,
+    /// Hardly any friction
+    Ice,
+    /// Gives back more speed than it takes
+    Bouncy,
+    /// Stops anything touching it almost at once
+    Sticky,
+    Custom {
+        friction: f32,
+        restitution: f32,
+    },
+}
+impl Surface {
+    pub fn material(&self) -> PhysicsMaterial {
+        match *self {
+            Surface::Normal => PhysicsMaterial::default(),
+            Surface::Ice => PhysicsMaterial {
+                friction: 0.01,
+                restitution: 0.1,
+            },
+            Surface::Bouncy => PhysicsMaterial {
+                friction: 0.18,
+                restitution: 1.1,
+            },
+            Surface::Sticky => PhysicsMaterial {
+                friction: 0.7,
+                restitution: 0.0,
+            },
+            Surface::Custom {
+                friction,
+                restitution,
+            } => PhysicsMaterial {
+                friction,
+                restitution,
+            },
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            Surface::Normal | Surface::Custom { .. } => {
+                Color::rgb(0.0, 0.0, 0.0)
+            }
+            Surface::Ice => Color::rgb(0.6, 0.85, 1.0),
+            Surface::Bouncy => Color::rgb(0.2, 0.8, 0.2),
+            Surface::Sticky => Color::rgb(0.5, 0.2, 0.5),
+        }
+    }
+}
+
 #[derive(Bundle)]
 pub struct BlockBundle {
     _block: Block,
-    _seeable: Seeable,
+    seeable: Seeable,
     spatial: SpatialBundle,
     sprite: Sprite,
     texture: Handle<Image>,
     hitbox: Hitbox,
+    material: PhysicsMaterial,
 }
 impl BlockBundle {
-    pub fn new(pos: Vec2, size: Vec2) -> BlockBundle {
+    /// `rotation` is in radians, counter-clockwise
+    pub fn new(
+        pos: Vec2,
+        size: Vec2,
+        rotation: f32,
+        surface: Surface,
+        layers: CollisionLayers,
+    ) -> BlockBundle {
         BlockBundle {
             _block: Block,
-            _seeable: Seeable,
+            seeable: Seeable {
+                kind: SeeableKind::Block,
+            },
             spatial: SpatialBundle {
                 transform: Transform {
                     translation: pos.extend(0.0),
-                    scale: size.extend(1.0),
+                    rotation: Quat::from_rotation_z(rotation),
                     ..default()
                 },
                 ..default()
             },
             sprite: Sprite {
-                color: Color::rgb(0.0, 0.0, 0.0),
+                color: surface.color(),
+                custom_size: Some(size),
                 ..default()
             },
             texture: DEFAULT_IMAGE_HANDLE.typed(),
             hitbox: Hitbox {
                 pos: Vec2 { x: 0.0, y: 0.0 },
                 size,
+                shape: Shape::Rect,
+                layers,
+            },
+            material: surface.material(),
+        }
+    }
+}
+
+/// A block of any convex shape, like a ramp
+#[derive(Bundle)]
+pub struct PolygonBlockBundle {
+    _block: Block,
+    seeable: Seeable,
+    mesh: MaterialMesh2dBundle<ColorMaterial>,
+    hitbox: Hitbox,
+    material: PhysicsMaterial,
+}
+impl PolygonBlockBundle {
+    /// `points` are relative to `pos`, and have to make a convex polygon
+    pub fn new(
+        pos: Vec2,
+        points: Vec<Vec2>,
+        surface: Surface,
+        layers: CollisionLayers,
+        meshes: &mut Assets<Mesh>,
+        materials: &mut Assets<ColorMaterial>,
+    ) -> PolygonBlockBundle {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            points.iter().map(|p| [p.x, p.y, 0.0]).collect::<Vec<_>>(),
+        );
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_NORMAL,
+            vec![[0.0, 0.0, 1.0]; points.len()],
+        );
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_UV_0,
+            vec![[0.0, 0.0]; points.len()],
+        );
+        // Convex, so a fan from the first point covers it
+        let indices = (1..points.len().saturating_sub(1) as u32)
+            .flat_map(|ix| [0, ix, ix + 1])
+            .collect();
+        mesh.set_indices(Some(Indices::U32(indices)));
+        PolygonBlockBundle {
+            _block: Block,
+            seeable: Seeable {
+                kind: SeeableKind::Block,
+            },
+            mesh: MaterialMesh2dBundle {
+                mesh: meshes.add(mesh).into(),
+                material: materials.add(ColorMaterial::from(surface.color())),
+                transform: Transform::from_translation(pos.extend(0.0)),
+                ..default()
+            },
+            hitbox: Hitbox {
+                layers,
+                ..Hitbox::polygon(points)
             },
+            material: surface.material(),
         }
     }
 }
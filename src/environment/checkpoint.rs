@@ -0,0 +1,62 @@
+use bevy::prelude::*;
+
+use crate::{
+    agent::{Agent, Mortal},
+    physics::events::{physics_triggers, SensorBundle, TriggerEvent},
+};
+
+/// One step along the way through a level. Agents only get credit for
+/// touching checkpoints in order.
+#[derive(Component)]
+pub struct Checkpoint {
+    pub order: usize,
+    /// Added to an agent's reward the first time it reaches this in order
+    pub reward: f32,
+}
+
+#[derive(Bundle)]
+pub struct CheckpointBundle {
+    checkpoint: Checkpoint,
+    sensor: SensorBundle,
+}
+impl CheckpointBundle {
+    pub fn new(
+        order: usize,
+        pos: Vec2,
+        size: Vec2,
+        reward: f32,
+    ) -> CheckpointBundle {
+        CheckpointBundle {
+            checkpoint: Checkpoint { order, reward },
+            sensor: SensorBundle::new(
+                pos,
+                size,
+                Color::rgba(0.2, 0.6, 1.0, 0.4),
+            ),
+        }
+    }
+}
+
+pub fn checkpoint_reach(
+    mut triggers: EventReader<TriggerEvent>,
+    mut agents: Query<Mortal, With<Agent>>,
+    checkpoints: Query<&Checkpoint>,
+) {
+    for ev in triggers.iter().filter(|ev| ev.is_inside()) {
+        let Ok(checkpoint) = checkpoints.get(ev.sensor) else {
+            continue;
+        };
+        let Ok(mut mortal) = agents.get_mut(ev.other) else {
+            continue;
+        };
+        if mortal.is_dead() || checkpoint.order != mortal.tracker.checkpoints {
+            continue;
+        }
+        mortal.tracker.checkpoints += 1;
+        mortal.tracker.bonus += checkpoint.reward;
+    }
+}
+
+pub fn register_checkpoint(app: &mut App) {
+    app.add_systems(Update, checkpoint_reach.after(physics_triggers));
+}
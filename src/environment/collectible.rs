@@ -0,0 +1,81 @@
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+    agent::{select::SelectedAgent, Agent, Mortal},
+    physics::events::{physics_triggers, SensorBundle, TriggerEvent},
+};
+
+use super::consts::COLLECTIBLE_SIZE;
+
+/// A coin every agent can pick up once per episode. Picking it up doesn't
+/// remove it, since the rest of the roll still has to get to it.
+#[derive(Component)]
+pub struct Collectible {
+    pub id: usize,
+    /// Added to the reward of each agent that picks it up
+    pub value: f32,
+}
+
+/// The ids of every collectible an agent has picked up
+#[derive(Component, Default)]
+pub struct Collected(pub HashSet<usize>);
+
+#[derive(Bundle)]
+pub struct CollectibleBundle {
+    collectible: Collectible,
+    sensor: SensorBundle,
+}
+impl CollectibleBundle {
+    pub fn new(id: usize, pos: Vec2, value: f32) -> CollectibleBundle {
+        CollectibleBundle {
+            collectible: Collectible { id, value },
+            sensor: SensorBundle::new(
+                pos,
+                COLLECTIBLE_SIZE,
+                Color::rgb(1.0, 0.8, 0.0),
+            ),
+        }
+    }
+}
+
+pub fn collectible_pickup(
+    mut triggers: EventReader<TriggerEvent>,
+    mut agents: Query<(Mortal, &mut Collected), With<Agent>>,
+    collectibles: Query<&Collectible>,
+) {
+    for ev in triggers.iter().filter(|ev| ev.is_inside()) {
+        let Ok(coin) = collectibles.get(ev.sensor) else {
+            continue;
+        };
+        let Ok((mut mortal, mut collected)) = agents.get_mut(ev.other) else {
+            continue;
+        };
+        if mortal.is_dead() || !collected.0.insert(coin.id) {
+            continue;
+        }
+        mortal.tracker.collected += 1;
+        mortal.tracker.bonus += coin.value;
+    }
+}
+
+/// Hides the coins the selected agent has already picked up
+pub fn collectible_display(
+    selected: Res<SelectedAgent>,
+    agents: Query<&Collected, With<Agent>>,
+    mut collectibles: Query<(&Collectible, &mut Visibility)>,
+) {
+    let collected = selected.entity.and_then(|e| agents.get(e).ok());
+    for (coin, mut visibility) in collectibles.iter_mut() {
+        let taken = collected.is_some_and(|c| c.0.contains(&coin.id));
+        *visibility = if taken {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+    }
+}
+
+pub fn register_collectible(app: &mut App) {
+    app.add_systems(Update, collectible_pickup.after(physics_triggers))
+        .add_systems(Update, collectible_display);
+}
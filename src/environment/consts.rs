@@ -1 +1,7 @@
+use bevy::math::Vec2;
+
 pub const BLOCK_SIZE: f32 = 100.0;
+pub const LEVEL_VERSION: u32 = 1;
+pub const DEFAULT_LEVEL_PATH: &str = "levels/default.level.ron";
+pub const CRATE_RESTITUTION: f32 = 0.1;
+pub const COLLECTIBLE_SIZE: Vec2 = Vec2::new(20.0, 20.0);
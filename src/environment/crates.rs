@@ -0,0 +1,63 @@
+use bevy::{prelude::*, render::texture::DEFAULT_IMAGE_HANDLE};
+
+use crate::{
+    agent::eye::{Seeable, SeeableKind},
+    physics::{consts::Dir, Hitbox, Moveable, PrevPosition, Shape, Velocity},
+};
+
+use super::consts::CRATE_RESTITUTION;
+
+/// A block that falls, and that agents (and other crates) can push around
+#[derive(Component)]
+pub struct Crate;
+
+#[derive(Bundle)]
+pub struct CrateBundle {
+    _crate: Crate,
+    seeable: Seeable,
+    spatial: SpatialBundle,
+    sprite: Sprite,
+    texture: Handle<Image>,
+    hitbox: Hitbox,
+    moveable: Moveable,
+    velocity: Velocity,
+    prev_position: PrevPosition,
+}
+impl CrateBundle {
+    pub fn new(pos: Vec2, size: Vec2, mass: f32) -> CrateBundle {
+        CrateBundle {
+            _crate: Crate,
+            seeable: Seeable {
+                kind: SeeableKind::Block,
+            },
+            spatial: SpatialBundle {
+                transform: Transform {
+                    translation: pos.extend(0.0),
+                    ..default()
+                },
+                ..default()
+            },
+            sprite: Sprite {
+                color: Color::rgb(0.55, 0.35, 0.15),
+                custom_size: Some(size),
+                ..default()
+            },
+            texture: DEFAULT_IMAGE_HANDLE.typed(),
+            hitbox: Hitbox {
+                pos: Vec2 { x: 0.0, y: 0.0 },
+                size,
+                shape: Shape::Rect,
+                ..default()
+            },
+            moveable: Moveable {
+                gravity_enabled: true,
+                gravity_scale: 1.0,
+                gravity_dir: Dir::Down,
+                mass,
+                restitution: CRATE_RESTITUTION,
+            },
+            velocity: Velocity { x: 0.0, y: 0.0 },
+            prev_position: PrevPosition(pos),
+        }
+    }
+}
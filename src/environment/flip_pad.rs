@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+
+use crate::physics::{
+    events::{physics_triggers, SensorBundle, TriggerEvent, TriggerPhase},
+    Moveable,
+};
+
+/// Reverses the gravity of whatever moves into it
+#[derive(Component)]
+pub struct FlipPad;
+
+#[derive(Bundle)]
+pub struct FlipPadBundle {
+    flip_pad: FlipPad,
+    sensor: SensorBundle,
+}
+impl FlipPadBundle {
+    pub fn new(pos: Vec2, size: Vec2) -> FlipPadBundle {
+        FlipPadBundle {
+            flip_pad: FlipPad,
+            sensor: SensorBundle::new(
+                pos,
+                size,
+                Color::rgba(0.3, 0.9, 0.7, 0.5),
+            ),
+        }
+    }
+}
+
+/// Only flips on the way in, so staying inside doesn't keep flipping back
+pub fn flip_pad_trigger(
+    mut triggers: EventReader<TriggerEvent>,
+    pads: Query<(), With<FlipPad>>,
+    mut movers: Query<&mut Moveable>,
+) {
+    for ev in triggers.iter() {
+        if ev.phase != TriggerPhase::Enter || !pads.contains(ev.sensor) {
+            continue;
+        }
+        let Ok(mut moveable) = movers.get_mut(ev.other) else {
+            continue;
+        };
+        if !moveable.gravity_enabled {
+            // Dead agents stay put
+            continue;
+        }
+        moveable.gravity_dir = moveable.gravity_dir.flipped();
+    }
+}
+
+pub fn register_flip_pad(app: &mut App) {
+    app.add_systems(Update, flip_pad_trigger.after(physics_triggers));
+}
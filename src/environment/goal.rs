@@ -0,0 +1,62 @@
+use bevy::prelude::*;
+
+use crate::{
+    agent::{Agent, DeathCause, Mortal},
+    environment::reward::reward_do_judgement,
+    physics::events::{physics_triggers, SensorBundle, TriggerEvent},
+};
+
+/// Reaching this ends an agent's episode successfully
+#[derive(Component)]
+pub struct Goal {
+    /// Added to the reward of every agent that reaches it
+    pub bonus: f32,
+}
+
+#[derive(Bundle)]
+pub struct GoalBundle {
+    goal: Goal,
+    sensor: SensorBundle,
+}
+impl GoalBundle {
+    pub fn new(pos: Vec2, size: Vec2, bonus: f32) -> GoalBundle {
+        GoalBundle {
+            goal: Goal { bonus },
+            sensor: SensorBundle::new(
+                pos,
+                size,
+                Color::rgba(1.0, 0.85, 0.0, 0.6),
+            ),
+        }
+    }
+}
+
+pub fn goal_reach(
+    mut triggers: EventReader<TriggerEvent>,
+    mut agents: Query<Mortal, With<Agent>>,
+    goals: Query<&Goal>,
+) {
+    for ev in triggers.iter().filter(|ev| ev.is_inside()) {
+        let Ok(goal) = goals.get(ev.sensor) else {
+            continue;
+        };
+        let Ok(mut mortal) = agents.get_mut(ev.other) else {
+            continue;
+        };
+        if mortal.is_dead() {
+            continue;
+        }
+        mortal.tracker.goal_reached = true;
+        mortal.tracker.bonus += goal.bonus;
+        mortal.kill(DeathCause::GoalReached);
+    }
+}
+
+pub fn register_goal(app: &mut App) {
+    app.add_systems(
+        Update,
+        goal_reach
+            .after(physics_triggers)
+            .before(reward_do_judgement),
+    );
+}
@@ -0,0 +1,108 @@
+use bevy::{prelude::*, render::texture::DEFAULT_IMAGE_HANDLE};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    agent::{
+        eye::{Seeable, SeeableKind},
+        Agent, DeathCause, Mortal,
+    },
+    environment::reward::reward_do_judgement,
+    physics::{events::CollisionEvent, physics_collide, Hitbox, Shape},
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HazardKind {
+    Spikes,
+    Lava,
+}
+impl HazardKind {
+    pub fn color(&self) -> Color {
+        match self {
+            HazardKind::Spikes => Color::rgb(0.5, 0.5, 0.5),
+            HazardKind::Lava => Color::rgb(1.0, 0.3, 0.0),
+        }
+    }
+}
+
+/// Kills any agent that touches it
+#[derive(Component)]
+pub struct Hazard {
+    pub kind: HazardKind,
+    /// Taken off the reward of the agent it kills
+    pub penalty: f32,
+}
+
+#[derive(Bundle)]
+pub struct HazardBundle {
+    hazard: Hazard,
+    seeable: Seeable,
+    spatial: SpatialBundle,
+    sprite: Sprite,
+    texture: Handle<Image>,
+    hitbox: Hitbox,
+}
+impl HazardBundle {
+    pub fn new(
+        kind: HazardKind,
+        pos: Vec2,
+        size: Vec2,
+        penalty: f32,
+    ) -> HazardBundle {
+        HazardBundle {
+            hazard: Hazard { kind, penalty },
+            seeable: Seeable {
+                kind: SeeableKind::Hazard,
+            },
+            spatial: SpatialBundle {
+                transform: Transform {
+                    translation: pos.extend(0.0),
+                    ..default()
+                },
+                ..default()
+            },
+            sprite: Sprite {
+                color: kind.color(),
+                custom_size: Some(size),
+                ..default()
+            },
+            texture: DEFAULT_IMAGE_HANDLE.typed(),
+            hitbox: Hitbox {
+                pos: Vec2 { x: 0.0, y: 0.0 },
+                size,
+                shape: Shape::Rect,
+                ..default()
+            },
+        }
+    }
+}
+
+/// Collisions push agents back out of hazards, so this goes off whatever
+/// `physics_collide` says they bumped into
+pub fn hazard_kill(
+    mut collisions: EventReader<CollisionEvent>,
+    mut agents: Query<Mortal, With<Agent>>,
+    hazards: Query<&Hazard>,
+) {
+    for ev in collisions.iter() {
+        let Ok(hazard) = hazards.get(ev.b) else {
+            continue;
+        };
+        let Ok(mut mortal) = agents.get_mut(ev.a) else {
+            continue;
+        };
+        if mortal.is_dead() {
+            continue;
+        }
+        mortal.tracker.penalty += hazard.penalty;
+        mortal.kill(DeathCause::Hazard);
+    }
+}
+
+pub fn register_hazard(app: &mut App) {
+    app.add_systems(
+        Update,
+        hazard_kill
+            .after(physics_collide)
+            .before(reward_do_judgement),
+    );
+}
@@ -0,0 +1,444 @@
+//! Levels are described by data files in `assets/levels` and loaded through
+//! the asset server, so making a new level doesn't need a recompile.
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    agent::AgentBody,
+    physics::{
+        collisions::is_convex,
+        consts::{LAYER_AGENT, LAYER_ALL},
+        kinematic::{Kinematic, PlatformPath},
+        CollisionLayers, OneWay, Velocity,
+    },
+};
+
+use super::{
+    block::{BlockBundle, PolygonBlockBundle, Surface},
+    checkpoint::CheckpointBundle,
+    collectible::CollectibleBundle,
+    consts::{DEFAULT_LEVEL_PATH, LEVEL_VERSION},
+    crates::CrateBundle,
+    flip_pad::FlipPadBundle,
+    goal::GoalBundle,
+    hazard::{HazardBundle, HazardKind},
+    reward::{
+        default_reward_terms, DistanceRewardBundle, FitnessMode, RewardTerm,
+    },
+    zone::{ZoneBundle, ZoneKind},
+};
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct BlockInfo {
+    pub pos: Vec2,
+    pub size: Vec2,
+    /// Radians, counter-clockwise
+    #[serde(default)]
+    pub rotation: f32,
+    #[serde(default)]
+    pub surface: Surface,
+    #[serde(default)]
+    pub layers: CollisionLayers,
+    /// Only solid from above, so agents can jump up through it
+    #[serde(default)]
+    pub one_way: bool,
+}
+
+/// A block of any convex shape, for ramps and slanted walls
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PolygonInfo {
+    pub pos: Vec2,
+    /// Relative to `pos`, in order around the edge
+    pub points: Vec<Vec2>,
+    #[serde(default)]
+    pub surface: Surface,
+    #[serde(default)]
+    pub layers: CollisionLayers,
+}
+
+/// A block that falls and can be pushed around
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CrateInfo {
+    pub pos: Vec2,
+    pub size: Vec2,
+    pub mass: f32,
+}
+
+/// A block that moves along a path, carrying whatever stands on it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlatformInfo {
+    pub size: Vec2,
+    pub path: PlatformPath,
+    pub speed: f32,
+    #[serde(default)]
+    pub surface: Surface,
+    #[serde(default)]
+    pub layers: CollisionLayers,
+    #[serde(default)]
+    pub one_way: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HazardInfo {
+    pub kind: HazardKind,
+    pub pos: Vec2,
+    pub size: Vec2,
+    #[serde(default)]
+    pub penalty: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GoalInfo {
+    pub pos: Vec2,
+    pub size: Vec2,
+    #[serde(default)]
+    pub bonus: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CheckpointInfo {
+    pub pos: Vec2,
+    pub size: Vec2,
+    #[serde(default)]
+    pub reward: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CollectibleInfo {
+    pub pos: Vec2,
+    pub value: f32,
+}
+
+/// Reverses the gravity of whatever moves into it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FlipPadInfo {
+    pub pos: Vec2,
+    pub size: Vec2,
+}
+
+/// An area that changes physics for whatever is inside it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ZoneInfo {
+    pub pos: Vec2,
+    pub size: Vec2,
+    pub kind: ZoneKind,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum RewardInfo {
+    Distance { pos: Vec2 },
+}
+
+/// The area agents are allowed to be in. Anything outside of it is out of
+/// bounds.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LevelBounds {
+    pub pos: Vec2,
+    pub size: Vec2,
+}
+impl LevelBounds {
+    pub fn contains(&self, point: Vec2) -> bool {
+        let min = self.pos - self.size / 2.0;
+        let max = self.pos + self.size / 2.0;
+        min.x <= point.x
+            && point.x < max.x
+            && min.y <= point.y
+            && point.y <= max.y
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PopulationInfo {
+    pub size: u32,
+    /// Whether agents bump into each other, or just pass through
+    #[serde(default)]
+    pub agents_collide: bool,
+    #[serde(default)]
+    pub body: AgentBody,
+    /// What layers agents are on and bump into, if not the usual ones, so
+    /// a level can have blocks that only stop a certain kind of agent
+    #[serde(default)]
+    pub layers: Option<CollisionLayers>,
+}
+impl PopulationInfo {
+    pub fn layers(&self) -> CollisionLayers {
+        let mut layers = self.layers.unwrap_or(CollisionLayers {
+            layer: LAYER_AGENT,
+            mask: LAYER_ALL,
+        });
+        if !self.agents_collide {
+            layers.mask &= !layers.layer;
+        }
+        layers
+    }
+}
+
+/// Kills agents that haven't gotten a better reward or moved at least
+/// `min_distance` within `seconds`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StagnationInfo {
+    pub seconds: f32,
+    pub min_distance: f32,
+    #[serde(default)]
+    pub min_reward_gain: f32,
+}
+
+/// Rules for ending an episode while the agent is still alive
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EpisodeInfo {
+    #[serde(default)]
+    pub time_limit: Option<f32>,
+    #[serde(default)]
+    pub stagnation: Option<StagnationInfo>,
+}
+
+#[derive(Serialize, Deserialize, TypeUuid, TypePath, Clone, Debug)]
+#[uuid = "75c024ff-8008-46d7-8b8f-6e90cba67268"]
+pub struct Level {
+    pub version: u32,
+    pub spawn_point: Vec2,
+    pub bounds: LevelBounds,
+    pub population: PopulationInfo,
+    #[serde(default)]
+    pub episode: EpisodeInfo,
+    pub blocks: Vec<BlockInfo>,
+    #[serde(default)]
+    pub polygons: Vec<PolygonInfo>,
+    #[serde(default)]
+    pub platforms: Vec<PlatformInfo>,
+    #[serde(default)]
+    pub crates: Vec<CrateInfo>,
+    #[serde(default)]
+    pub hazards: Vec<HazardInfo>,
+    #[serde(default)]
+    pub goals: Vec<GoalInfo>,
+    /// Have to be reached in the order they're listed
+    #[serde(default)]
+    pub checkpoints: Vec<CheckpointInfo>,
+    #[serde(default)]
+    pub collectibles: Vec<CollectibleInfo>,
+    #[serde(default)]
+    pub zones: Vec<ZoneInfo>,
+    #[serde(default)]
+    pub flip_pads: Vec<FlipPadInfo>,
+    /// Fraction of a generation that has to reach a goal for the level to
+    /// count as solved
+    #[serde(default = "default_solve_fraction")]
+    pub solve_fraction: f32,
+    pub rewards: Vec<RewardInfo>,
+    /// How fitness is computed, summed over every term
+    #[serde(default = "default_reward_terms")]
+    pub reward_terms: Vec<RewardTerm>,
+    /// How each agent's rewards over an episode become its fitness
+    #[serde(default)]
+    pub fitness_mode: FitnessMode,
+}
+
+fn default_solve_fraction() -> f32 {
+    0.5
+}
+
+#[derive(Default)]
+pub struct LevelLoader;
+impl AssetLoader for LevelLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let level = ron::de::from_bytes::<Level>(bytes)?;
+            if level.version != LEVEL_VERSION {
+                return Err(bevy::asset::Error::msg(format!(
+                    "level {:?} has version {}, expected {}",
+                    load_context.path(),
+                    level.version,
+                    LEVEL_VERSION
+                )));
+            }
+            if let Some(ix) =
+                level.polygons.iter().position(|p| !is_convex(&p.points))
+            {
+                return Err(bevy::asset::Error::msg(format!(
+                    "level {:?} has polygon {} that isn't convex",
+                    load_context.path(),
+                    ix
+                )));
+            }
+            load_context.set_default_asset(LoadedAsset::new(level));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+/// The level that is currently being played
+#[derive(Resource)]
+pub struct CurrentLevel {
+    pub handle: Handle<Level>,
+}
+
+/// Marks everything that was spawned from a level file, so it can be cleaned
+/// up when the level changes
+#[derive(Component)]
+pub struct LevelEntity;
+
+pub fn spawn_level(
+    commands: &mut Commands,
+    level: &Level,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+) {
+    for block in level.blocks.iter() {
+        let mut entity = commands.spawn((
+            BlockBundle::new(
+                block.pos,
+                block.size,
+                block.rotation,
+                block.surface,
+                block.layers,
+            ),
+            LevelEntity,
+        ));
+        if block.one_way {
+            entity.insert(OneWay);
+        }
+    }
+    for polygon in level.polygons.iter() {
+        commands.spawn((
+            PolygonBlockBundle::new(
+                polygon.pos,
+                polygon.points.clone(),
+                polygon.surface,
+                polygon.layers,
+                meshes,
+                materials,
+            ),
+            LevelEntity,
+        ));
+    }
+    for platform in level.platforms.iter() {
+        let mut entity = commands.spawn((
+            BlockBundle::new(
+                platform.path.start(),
+                platform.size,
+                0.0,
+                platform.surface,
+                platform.layers,
+            ),
+            Kinematic::new(&platform.path, platform.speed),
+            Velocity { x: 0.0, y: 0.0 },
+            LevelEntity,
+        ));
+        if platform.one_way {
+            entity.insert(OneWay);
+        }
+    }
+    for crate_info in level.crates.iter() {
+        commands.spawn((
+            CrateBundle::new(crate_info.pos, crate_info.size, crate_info.mass),
+            LevelEntity,
+        ));
+    }
+    for hazard in level.hazards.iter() {
+        commands.spawn((
+            HazardBundle::new(
+                hazard.kind,
+                hazard.pos,
+                hazard.size,
+                hazard.penalty,
+            ),
+            LevelEntity,
+        ));
+    }
+    for goal in level.goals.iter() {
+        commands.spawn((
+            GoalBundle::new(goal.pos, goal.size, goal.bonus),
+            LevelEntity,
+        ));
+    }
+    for (order, checkpoint) in level.checkpoints.iter().enumerate() {
+        commands.spawn((
+            CheckpointBundle::new(
+                order,
+                checkpoint.pos,
+                checkpoint.size,
+                checkpoint.reward,
+            ),
+            LevelEntity,
+        ));
+    }
+    for (id, collectible) in level.collectibles.iter().enumerate() {
+        commands.spawn((
+            CollectibleBundle::new(id, collectible.pos, collectible.value),
+            LevelEntity,
+        ));
+    }
+    for zone in level.zones.iter() {
+        commands.spawn((
+            ZoneBundle::new(zone.pos, zone.size, zone.kind),
+            LevelEntity,
+        ));
+    }
+    for pad in level.flip_pads.iter() {
+        commands.spawn((FlipPadBundle::new(pad.pos, pad.size), LevelEntity));
+    }
+    for reward in level.rewards.iter() {
+        match reward {
+            RewardInfo::Distance { pos } => {
+                commands.spawn((DistanceRewardBundle::new(*pos), LevelEntity));
+            }
+        }
+    }
+}
+
+pub fn level_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(CurrentLevel {
+        handle: asset_server.load(DEFAULT_LEVEL_PATH),
+    });
+}
+
+/// (Re)spawns the level entities whenever the current level finishes loading
+pub fn level_spawn(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<Level>>,
+    levels: Res<Assets<Level>>,
+    current: Res<CurrentLevel>,
+    existing: Query<Entity, With<LevelEntity>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for ev in events.iter() {
+        let handle = match ev {
+            AssetEvent::Created { handle }
+            | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        if *handle != current.handle {
+            continue;
+        }
+        let Some(level) = levels.get(handle) else {
+            continue;
+        };
+        for entity in existing.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        spawn_level(&mut commands, level, &mut meshes, &mut materials);
+    }
+}
+
+pub fn register_level(app: &mut App) {
+    app.add_asset::<Level>()
+        .init_asset_loader::<LevelLoader>()
+        .add_systems(Startup, level_setup)
+        .add_systems(Update, level_spawn);
+}
@@ -1,30 +1,30 @@
 pub mod block;
+pub mod checkpoint;
+pub mod collectible;
 pub mod consts;
+pub mod crates;
+pub mod flip_pad;
+pub mod goal;
+pub mod hazard;
+pub mod level;
 pub mod reward;
+pub mod zone;
 
 use bevy::prelude::*;
 
 use self::{
-    block::BlockBundle,
-    reward::{register_reward, DistanceRewardBundle},
+    checkpoint::register_checkpoint, collectible::register_collectible,
+    flip_pad::register_flip_pad, goal::register_goal, hazard::register_hazard,
+    level::register_level, reward::register_reward, zone::register_zone,
 };
 
-pub fn environment_setup(mut commands: Commands) {
-    commands.spawn(BlockBundle::new(
-        Vec2 { x: 0.0, y: -50.0 },
-        Vec2 {
-            x: 1000.0,
-            y: 100.0,
-        },
-    ));
-    commands.spawn(BlockBundle::new(
-        Vec2 { x: 0.0, y: 50.0 },
-        Vec2 { x: 100.0, y: 100.0 },
-    ));
-    commands.spawn(DistanceRewardBundle::new(Vec2 { x: -10.0, y: -75.0 }));
-}
-
 pub fn register_environment(app: &mut App) {
-    app.add_systems(Startup, environment_setup);
+    register_level(app);
     register_reward(app);
+    register_hazard(app);
+    register_goal(app);
+    register_checkpoint(app);
+    register_collectible(app);
+    register_zone(app);
+    register_flip_pad(app);
 }
@@ -1,6 +1,12 @@
 use bevy::{prelude::*, render::texture::DEFAULT_IMAGE_HANDLE};
+use serde::{Deserialize, Serialize};
 
-use crate::physics::physics_collide;
+use crate::{
+    agent::{AgentAnimState, Death},
+    animation::AnimationVal,
+    environment::level::{CurrentLevel, Level},
+    physics::physics_collide,
+};
 
 #[derive(Component)]
 pub struct DistanceReward;
@@ -32,25 +38,240 @@ impl DistanceRewardBundle {
     }
 }
 
-#[derive(Component)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum DistanceMode {
+    Approach,
+    Flee,
+}
+
+/// One ingredient of fitness. Levels combine weighted terms to decide what
+/// doing well means.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum RewardKind {
+    /// Summed distance to every `DistanceReward`
+    Distance(DistanceMode),
+    /// How much closer than where it started the agent has ever gotten
+    BestProgress,
+    /// Seconds spent alive
+    TimeAlive,
+    /// 1 once the agent has touched a `Goal`, or been within `radius` of a
+    /// `DistanceReward`
+    GoalReached { radius: f32 },
+    /// Number of checkpoints reached in order
+    Checkpoints,
+    /// Number of collectibles picked up
+    Collectibles,
+    /// 1 once the agent has died, so give it a negative weight
+    Death,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RewardTerm {
+    pub kind: RewardKind,
+    pub weight: f32,
+}
+impl RewardTerm {
+    pub fn evaluate(&self, tracker: &RewardTracker) -> f32 {
+        let value = match self.kind {
+            RewardKind::Distance(DistanceMode::Approach) => -tracker.distance,
+            RewardKind::Distance(DistanceMode::Flee) => tracker.distance,
+            RewardKind::BestProgress => {
+                match (tracker.start_distance, tracker.best_distance) {
+                    (Some(start), Some(best)) => start - best,
+                    _ => 0.0,
+                }
+            }
+            RewardKind::TimeAlive => tracker.time_alive,
+            RewardKind::GoalReached { .. } => {
+                if tracker.goal_reached {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            RewardKind::Checkpoints => tracker.checkpoints as f32,
+            RewardKind::Collectibles => tracker.collected as f32,
+            RewardKind::Death => {
+                if tracker.dead {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        };
+        self.weight * value
+    }
+}
+
+/// Matches the original behavior of rewarding distance from the rewards
+pub fn default_reward_terms() -> Vec<RewardTerm> {
+    vec![RewardTerm {
+        kind: RewardKind::Distance(DistanceMode::Flee),
+        weight: 1.0,
+    }]
+}
+
+/// How an episode's rewards are boiled down into a single fitness
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum FitnessMode {
+    /// Reward integrated over the episode
+    Sum,
+    /// Sum divided by the length of the episode
+    Mean,
+    /// Best reward at any point in the episode
+    Max,
+    /// Reward at the end of the episode
+    #[default]
+    Final,
+}
+
+#[derive(Component, Default)]
 pub struct Judgeable {
+    /// Reward this frame
     pub reward: f32,
+    /// Reward integrated over time
+    pub sum: f32,
+    pub max: Option<f32>,
+    /// Seconds into the episode when `max` was reached
+    pub time_of_best: f32,
+    pub duration: f32,
+    /// Once over, the episode's numbers are frozen
+    pub finished: bool,
+}
+impl Judgeable {
+    pub fn record(&mut self, reward: f32, dt: f32) {
+        self.reward = reward;
+        self.sum += reward * dt;
+        self.duration += dt;
+        if self.max.is_none_or(|max| reward > max) {
+            self.max = Some(reward);
+            self.time_of_best = self.duration;
+        }
+    }
+
+    pub fn fitness(&self, mode: FitnessMode) -> f32 {
+        match mode {
+            FitnessMode::Sum => self.sum,
+            FitnessMode::Mean => {
+                if self.duration > 0.0 {
+                    self.sum / self.duration
+                } else {
+                    self.reward
+                }
+            }
+            FitnessMode::Max => self.max.unwrap_or(self.reward),
+            FitnessMode::Final => self.reward,
+        }
+    }
+}
+
+/// Sent once when a judgeable's episode is over
+#[derive(Event)]
+pub struct EpisodeEnded {
+    pub entity: Entity,
+    pub fitness: f32,
+    pub duration: f32,
+}
+
+/// Everything about a judgeable's episode that reward terms can look at
+#[derive(Component, Default)]
+pub struct RewardTracker {
+    /// Current summed distance to every `DistanceReward`
+    pub distance: f32,
+    pub start_distance: Option<f32>,
+    pub best_distance: Option<f32>,
+    pub time_alive: f32,
+    pub goal_reached: bool,
+    /// How many checkpoints have been reached in order, which is also the
+    /// order of the next one
+    pub checkpoints: usize,
+    pub collected: u32,
+    pub dead: bool,
+    /// Added straight onto the reward, whatever the terms are
+    pub bonus: f32,
+    /// Taken straight off the reward, whatever the terms are
+    pub penalty: f32,
 }
 
 pub fn reward_do_judgement(
-    mut subjects: Query<(&mut Judgeable, &GlobalTransform)>,
+    time: Res<Time>,
+    current_level: Res<CurrentLevel>,
+    levels: Res<Assets<Level>>,
+    mut episode_ended: EventWriter<EpisodeEnded>,
+    mut subjects: Query<(
+        Entity,
+        &mut Judgeable,
+        &mut RewardTracker,
+        &GlobalTransform,
+        Option<&AnimationVal<AgentAnimState>>,
+        Option<&mut Death>,
+    )>,
     drs: Query<&GlobalTransform, With<DistanceReward>>,
 ) {
-    for (mut sub_score, sub_trans) in subjects.iter_mut() {
-        let mut cum_reward = 0.0;
+    let Some(level) = levels.get(&current_level.handle) else {
+        return;
+    };
+    for (entity, mut sub_score, mut tracker, sub_trans, anim_val, death) in
+        subjects.iter_mut()
+    {
+        if sub_score.finished {
+            continue;
+        }
+        tracker.dead = anim_val
+            .is_some_and(|anim_val| anim_val.state == AgentAnimState::Dead);
+        if !tracker.dead {
+            tracker.time_alive += time.delta_seconds();
+        }
+
+        let mut cum_distance = 0.0;
+        let mut nearest = f32::MAX;
         for dr_trans in drs.iter() {
-            cum_reward +=
+            let dist =
                 sub_trans.translation().distance(dr_trans.translation());
+            cum_distance += dist;
+            nearest = nearest.min(dist);
+        }
+        tracker.distance = cum_distance;
+        if tracker.start_distance.is_none() {
+            tracker.start_distance = Some(cum_distance);
+        }
+        tracker.best_distance = Some(
+            tracker
+                .best_distance
+                .map_or(cum_distance, |best| best.min(cum_distance)),
+        );
+        for term in level.reward_terms.iter() {
+            if let RewardKind::GoalReached { radius } = term.kind {
+                tracker.goal_reached |= nearest <= radius;
+            }
+        }
+
+        let reward: f32 = level
+            .reward_terms
+            .iter()
+            .map(|term| term.evaluate(&tracker))
+            .sum::<f32>()
+            + tracker.bonus
+            - tracker.penalty;
+        sub_score.record(reward, time.delta_seconds());
+        if tracker.dead {
+            sub_score.finished = true;
+            // The record was taken before this frame's reward was in
+            if let Some(record) =
+                death.and_then(|death| death.into_inner().record.as_mut())
+            {
+                record.reward = sub_score.reward;
+            }
+            episode_ended.send(EpisodeEnded {
+                entity,
+                fitness: sub_score.fitness(level.fitness_mode),
+                duration: tracker.time_alive,
+            });
         }
-        sub_score.reward = cum_reward;
     }
 }
 
 pub fn register_reward(app: &mut App) {
-    app.add_systems(Update, reward_do_judgement.after(physics_collide));
+    app.add_event::<EpisodeEnded>()
+        .add_systems(Update, reward_do_judgement.after(physics_collide));
 }
@@ -0,0 +1,100 @@
+//! Areas that change how physics works for whatever is inside them
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::physics::{
+    consts::GRAVITY,
+    events::{Sensor, SensorBundle},
+    physics_delta, physics_gravity, physics_move, Moveable, Velocity,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum ZoneKind {
+    /// Pushes everything inside with a constant force
+    Wind { force: Vec2 },
+    /// Holds things up against a fraction of their gravity, whichever way
+    /// it goes, and slows them down by `drag` of their speed every second
+    Water { buoyancy: f32, drag: f32 },
+    /// Multiplies the gravity of everything inside
+    Gravity { scale: f32 },
+}
+impl ZoneKind {
+    pub fn color(&self) -> Color {
+        match self {
+            ZoneKind::Wind { .. } => Color::rgba(0.9, 0.9, 0.9, 0.2),
+            ZoneKind::Water { .. } => Color::rgba(0.1, 0.4, 0.9, 0.35),
+            ZoneKind::Gravity { .. } => Color::rgba(0.6, 0.3, 0.9, 0.25),
+        }
+    }
+}
+
+/// Applies its kind to whatever its `Sensor` last saw inside
+#[derive(Component)]
+pub struct Zone {
+    pub kind: ZoneKind,
+}
+
+#[derive(Bundle)]
+pub struct ZoneBundle {
+    zone: Zone,
+    sensor: SensorBundle,
+}
+impl ZoneBundle {
+    pub fn new(pos: Vec2, size: Vec2, kind: ZoneKind) -> ZoneBundle {
+        ZoneBundle {
+            zone: Zone { kind },
+            sensor: SensorBundle::new(pos, size, kind.color()),
+        }
+    }
+}
+
+/// Goes on top of normal gravity, so it's already been applied once
+pub fn zone_apply(
+    time: Res<Time>,
+    zones: Query<(&Zone, &Sensor)>,
+    mut movers: Query<(&mut Velocity, &Moveable)>,
+) {
+    let dt = physics_delta(&time);
+    for (zone, sensor) in zones.iter() {
+        let mut inside = movers.iter_many_mut(sensor.overlapping.iter());
+        while let Some((mut velocity, moveable)) = inside.fetch_next() {
+            if !moveable.gravity_enabled {
+                // Dead agents and such stay where they are
+                continue;
+            }
+            let gravity = moveable.gravity_dir.vector()
+                * GRAVITY
+                * moveable.gravity_scale
+                * dt;
+            match zone.kind {
+                ZoneKind::Wind { force } => {
+                    // Zero (or less) mass counts as infinitely heavy
+                    if moveable.mass > 0.0 {
+                        let push = force * dt / moveable.mass;
+                        velocity.x += push.x;
+                        velocity.y += push.y;
+                    }
+                }
+                ZoneKind::Water { buoyancy, drag } => {
+                    velocity.x -= buoyancy * gravity.x;
+                    velocity.y -= buoyancy * gravity.y;
+                    let keep = (1.0 - drag * dt).max(0.0);
+                    velocity.x *= keep;
+                    velocity.y *= keep;
+                }
+                ZoneKind::Gravity { scale } => {
+                    velocity.x += (scale - 1.0) * gravity.x;
+                    velocity.y += (scale - 1.0) * gravity.y;
+                }
+            }
+        }
+    }
+}
+
+pub fn register_zone(app: &mut App) {
+    app.add_systems(
+        Update,
+        zone_apply.after(physics_gravity).before(physics_move),
+    );
+}
@@ -0,0 +1,23 @@
+// Bevy systems and their queries get long without being hard to read
+#![allow(clippy::type_complexity)]
+
+// Written before these lints, and left as they were
+#[allow(
+    clippy::approx_constant,
+    clippy::clone_on_copy,
+    clippy::excessive_precision,
+    clippy::needless_return,
+    clippy::needless_update
+)]
+pub mod agent;
+#[allow(
+    clippy::needless_borrow,
+    clippy::needless_return,
+    clippy::ptr_arg,
+    clippy::single_match
+)]
+pub mod animation;
+pub mod editor;
+pub mod environment;
+pub mod meta;
+pub mod physics;
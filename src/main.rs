@@ -1,18 +1,15 @@
-pub mod agent;
-pub mod animation;
-pub mod environment;
-pub mod meta;
-pub mod physics;
-
-use agent::register_agent;
-use animation::register_animations;
 use bevy::{prelude::*, window::WindowResolution};
-use environment::register_environment;
-use meta::{
-    consts::{WINDOW_HEIGHT, WINDOW_WIDTH},
-    register_meta,
+use pupil::{
+    agent::register_agent,
+    animation::register_animations,
+    editor::register_editor,
+    environment::register_environment,
+    meta::{
+        consts::{WINDOW_HEIGHT, WINDOW_WIDTH},
+        register_meta,
+    },
+    physics::register_physics,
 };
-use physics::register_physics;
 
 pub fn main_setup(
     mut commands: Commands,
@@ -41,5 +38,6 @@ fn main() {
     register_meta(&mut app);
     register_physics(&mut app);
     register_agent(&mut app);
+    register_editor(&mut app);
     app.run();
 }
@@ -1,2 +1,7 @@
+use bevy::math::Vec2;
+
 pub const WINDOW_WIDTH: f32 = 800.0;
 pub const WINDOW_HEIGHT: f32 = 600.0;
+pub const HUD_FONT_SIZE: f32 = 20.0;
+pub const CHART_MARGIN: f32 = 10.0;
+pub const CHART_SIZE: Vec2 = Vec2::new(240.0, 120.0);
@@ -0,0 +1,149 @@
+//! Shows how training is going while testing
+
+use bevy::prelude::*;
+
+use crate::{
+    agent::{roll::Roll, stats::RollHistory, Agent, AgentAnimState},
+    animation::AnimationVal,
+    environment::{
+        level::{CurrentLevel, Level},
+        reward::Judgeable,
+    },
+};
+
+use super::{
+    consts::{
+        CHART_MARGIN, CHART_SIZE, HUD_FONT_SIZE, WINDOW_HEIGHT, WINDOW_WIDTH,
+    },
+    LevelState, MetaState,
+};
+
+#[derive(Component)]
+pub struct Hud;
+
+pub fn hud_setup(mut commands: Commands) {
+    commands.spawn((
+        Hud,
+        TextBundle {
+            visibility: Visibility::Hidden,
+            ..TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: HUD_FONT_SIZE,
+                    color: Color::BLACK,
+                    ..default()
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(CHART_MARGIN),
+                left: Val::Px(CHART_MARGIN),
+                ..default()
+            })
+        },
+    ));
+}
+
+pub fn hud_update(
+    meta: Res<MetaState>,
+    mut hud: Query<(&mut Text, &mut Visibility), With<Hud>>,
+    roll: Query<&Roll>,
+    history: Res<RollHistory>,
+    agents: Query<(&AnimationVal<AgentAnimState>, &Judgeable), With<Agent>>,
+    current_level: Res<CurrentLevel>,
+    levels: Res<Assets<Level>>,
+) {
+    let Ok((mut text, mut visibility)) = hud.get_single_mut() else {
+        return;
+    };
+    let Ok(roll) = roll.get_single() else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    if meta.level_state != LevelState::Testing {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Visible;
+
+    let alive = agents
+        .iter()
+        .filter(|(anim_val, _)| anim_val.state != AgentAnimState::Dead)
+        .count();
+    // The same fitness that picks who gets to breed
+    let best = levels.get(&current_level.handle).and_then(|level| {
+        agents
+            .iter()
+            .map(|(_, judgeable)| judgeable.fitness(level.fitness_mode))
+            .reduce(f32::max)
+    });
+    text.sections[0].value = format!(
+        "Generation {}\nAlive {}/{}\nBest fitness {}",
+        roll.iteration(),
+        alive,
+        agents.iter().count(),
+        best.map_or("-".to_string(), |best| format!("{:.2}", best)),
+    );
+    if let Some(solved_at) = history.solved_at {
+        text.sections[0]
+            .value
+            .push_str(&format!("\nSolved in generation {}", solved_at));
+    }
+}
+
+/// Plots the best and mean fitness of every finished generation in the top
+/// right corner
+pub fn hud_draw_chart(
+    meta: Res<MetaState>,
+    history: Res<RollHistory>,
+    mut gizmos: Gizmos,
+) {
+    if meta.level_state != LevelState::Testing {
+        return;
+    }
+    // The camera never moves, so the corner of the window is fixed
+    let top_right = Vec2 {
+        x: WINDOW_WIDTH / 2.0 - CHART_MARGIN,
+        y: WINDOW_HEIGHT / 2.0 - CHART_MARGIN,
+    };
+    let center = top_right - CHART_SIZE / 2.0;
+    let bottom_left = top_right - CHART_SIZE;
+    gizmos.rect_2d(center, 0.0, CHART_SIZE, Color::GRAY);
+
+    let gens = &history.generations;
+    if gens.len() < 2 {
+        return;
+    }
+    let (lo, hi) = gens.iter().fold((f32::MAX, f32::MIN), |(lo, hi), gen| {
+        (
+            lo.min(gen.fitness.mean).min(gen.fitness.max),
+            hi.max(gen.fitness.mean).max(gen.fitness.max),
+        )
+    });
+    let range = if hi - lo > f32::EPSILON { hi - lo } else { 1.0 };
+    let to_chart = |ix: usize, value: f32| {
+        bottom_left
+            + Vec2 {
+                x: ix as f32 / (gens.len() - 1) as f32,
+                y: (value - lo) / range,
+            } * CHART_SIZE
+    };
+    gizmos.linestrip_2d(
+        gens.iter()
+            .enumerate()
+            .map(|(ix, gen)| to_chart(ix, gen.fitness.max)),
+        Color::GREEN,
+    );
+    gizmos.linestrip_2d(
+        gens.iter()
+            .enumerate()
+            .map(|(ix, gen)| to_chart(ix, gen.fitness.mean)),
+        Color::BLUE,
+    );
+}
+
+pub fn register_hud(app: &mut App) {
+    app.add_systems(Startup, hud_setup)
+        .add_systems(Update, hud_update)
+        .add_systems(Update, hud_draw_chart);
+}
@@ -1,11 +1,18 @@
-use crate::agent::{
-    delete_all_agents,
-    roll::{Roll, RollBundle},
-    Agent,
+use crate::{
+    agent::{
+        delete_all_agents,
+        roll::{Roll, RollBundle},
+        stats::RollHistory,
+        Agent,
+    },
+    environment::level::{CurrentLevel, Level},
 };
-use bevy::prelude::*;
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use self::hud::register_hud;
 
 pub mod consts;
+pub mod hud;
 
 #[derive(PartialEq, Debug)]
 pub enum LevelState {
@@ -24,12 +31,25 @@ pub fn meta_setup(mut commands: Commands) {
     });
 }
 
+/// Where the mouse is, in world coordinates
+pub fn cursor_world_pos(
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    cameras: &Query<(&Camera, &GlobalTransform)>,
+) -> Option<Vec2> {
+    let cursor = windows.get_single().ok()?.cursor_position()?;
+    let (camera, camera_t) = cameras.get_single().ok()?;
+    camera.viewport_to_world_2d(camera_t, cursor)
+}
+
 /// Resets the testing state
-fn meta_reset_testing(
-    commands: &mut Commands,
-    _meta: &mut ResMut<MetaState>, // Will eventually be used to pass size + spawn point info
-) {
-    commands.spawn(RollBundle::new(1, Vec2 { x: -50.0, y: 50.0 }));
+fn meta_reset_testing(commands: &mut Commands, level: &Level) {
+    commands.spawn(RollBundle::new(
+        level.population.size,
+        level.spawn_point,
+        level.population.layers(),
+        level.population.body,
+    ));
+    commands.insert_resource(RollHistory::new_run());
 }
 
 fn meta_continue_designing(
@@ -51,11 +71,17 @@ pub fn meta_handle_state_switch(
     input: Res<Input<KeyCode>>,
     rolls_query: Query<Entity, With<Roll>>,
     agents_query: Query<Entity, With<Agent>>,
+    current_level: Res<CurrentLevel>,
+    levels: Res<Assets<Level>>,
 ) {
     if input.just_pressed(KeyCode::Space) {
         if meta.level_state == LevelState::Designing {
+            let Some(level) = levels.get(&current_level.handle) else {
+                // Can't test a level that hasn't loaded yet
+                return;
+            };
             meta.level_state = LevelState::Testing;
-            meta_reset_testing(&mut commands, &mut meta);
+            meta_reset_testing(&mut commands, level);
         } else {
             meta.level_state = LevelState::Designing;
             meta_continue_designing(
@@ -71,4 +97,5 @@ pub fn meta_handle_state_switch(
 pub fn register_meta(app: &mut App) {
     app.add_systems(Startup, meta_setup);
     app.add_systems(Update, meta_handle_state_switch);
+    register_hud(app);
 }
@@ -0,0 +1,158 @@
+//! A uniform grid of hitboxes, so collisions and eyes only have to check
+//! the things that are near them instead of everything in the level
+
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{
+    consts::BROADPHASE_CELL_SIZE, events::Sensor, get_bounds, Hitbox, Moveable,
+};
+
+/// Buckets entities by which grid cells their bounds touch
+#[derive(Default)]
+pub struct SpatialHash {
+    cells: HashMap<(i32, i32), Vec<Entity>>,
+}
+impl SpatialHash {
+    fn cell_range(min: Vec2, max: Vec2) -> ((i32, i32), (i32, i32)) {
+        let lo = (min / BROADPHASE_CELL_SIZE).floor();
+        let hi = (max / BROADPHASE_CELL_SIZE).floor();
+        ((lo.x as i32, lo.y as i32), (hi.x as i32, hi.y as i32))
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    pub fn insert(&mut self, entity: Entity, min: Vec2, max: Vec2) {
+        let ((lx, ly), (hx, hy)) = SpatialHash::cell_range(min, max);
+        for x in lx..=hx {
+            for y in ly..=hy {
+                self.cells.entry((x, y)).or_default().push(entity);
+            }
+        }
+    }
+
+    pub fn insert_hitbox(
+        &mut self,
+        entity: Entity,
+        hitbox: &Hitbox,
+        transform: &Transform,
+    ) {
+        let (left, right, top, bot) = get_bounds(hitbox, transform);
+        self.insert(entity, Vec2::new(left, bot), Vec2::new(right, top));
+    }
+
+    /// Everything that might overlap the area, each entity only once
+    pub fn query(&self, min: Vec2, max: Vec2) -> Vec<Entity> {
+        let ((lx, ly), (hx, hy)) = SpatialHash::cell_range(min, max);
+        let mut found = vec![];
+        for x in lx..=hx {
+            for y in ly..=hy {
+                if let Some(entities) = self.cells.get(&(x, y)) {
+                    found.extend(entities.iter().copied());
+                }
+            }
+        }
+        found.sort();
+        found.dedup();
+        found
+    }
+
+    pub fn query_hitbox(
+        &self,
+        hitbox: &Hitbox,
+        transform: &Transform,
+    ) -> Vec<Entity> {
+        let (left, right, top, bot) = get_bounds(hitbox, transform);
+        self.query(Vec2::new(left, bot), Vec2::new(right, top))
+    }
+}
+
+/// The grids that get rebuilt every frame
+#[derive(Resource, Default)]
+pub struct Broadphase {
+    /// Everything moveable things can bump into
+    pub immovable: SpatialHash,
+    /// Everything eyes can see
+    pub seeable: SpatialHash,
+}
+
+/// Immoveable things only move in `physics_kinematic`, so after that the
+/// grid is good for the rest of the frame. Sensors never push anything, so
+/// they're left out.
+pub fn physics_broadphase(
+    mut broadphase: ResMut<Broadphase>,
+    q_immovable: Query<
+        (Entity, &Hitbox, &Transform),
+        (Without<Moveable>, Without<Sensor>),
+    >,
+) {
+    broadphase.immovable.clear();
+    for (entity, hitbox, transform) in q_immovable.iter() {
+        broadphase
+            .immovable
+            .insert_hitbox(entity, hitbox, transform);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_finds_only_nearby_entities() {
+        let (a, b) = (Entity::from_raw(0), Entity::from_raw(1));
+        let mut hash = SpatialHash::default();
+        hash.insert(a, Vec2::new(10.0, 10.0), Vec2::new(20.0, 20.0));
+        hash.insert(b, Vec2::new(510.0, 510.0), Vec2::new(520.0, 520.0));
+
+        assert_eq!(hash.query(Vec2::ZERO, Vec2::splat(50.0)), vec![a]);
+        assert_eq!(
+            hash.query(Vec2::splat(500.0), Vec2::splat(600.0)),
+            vec![b]
+        );
+        assert!(hash
+            .query(Vec2::splat(-300.0), Vec2::splat(-200.0))
+            .is_empty());
+
+        hash.clear();
+        assert!(hash.query(Vec2::ZERO, Vec2::splat(50.0)).is_empty());
+    }
+
+    #[test]
+    fn query_returns_each_entity_once() {
+        let (a, b) = (Entity::from_raw(0), Entity::from_raw(1));
+        let mut hash = SpatialHash::default();
+        // Both span several cells
+        hash.insert(b, Vec2::new(-150.0, -150.0), Vec2::new(150.0, 150.0));
+        hash.insert(a, Vec2::new(-50.0, -50.0), Vec2::new(250.0, 50.0));
+        assert_eq!(
+            hash.query(Vec2::splat(-200.0), Vec2::splat(200.0)),
+            vec![a, b]
+        );
+    }
+
+    #[test]
+    fn query_hitbox_uses_world_bounds() {
+        let entity = Entity::from_raw(7);
+        let hitbox = Hitbox {
+            pos: Vec2::new(5.0, 0.0),
+            size: Vec2::new(20.0, 10.0),
+            ..default()
+        };
+        let transform = Transform::from_xyz(300.0, 0.0, 0.0)
+            .with_scale(Vec3::new(2.0, 1.0, 1.0));
+        let mut hash = SpatialHash::default();
+        hash.insert_hitbox(entity, &hitbox, &transform);
+
+        // From x 290 to 330, so it sits in cells 2 and 3 only
+        let probe = Hitbox {
+            size: Vec2::splat(10.0),
+            ..default()
+        };
+        let near = Transform::from_xyz(320.0, 0.0, 0.0);
+        let far = Transform::from_xyz(150.0, 0.0, 0.0);
+        assert_eq!(hash.query_hitbox(&probe, &near), vec![entity]);
+        assert!(hash.query_hitbox(&probe, &far).is_empty());
+    }
+}
@@ -1,5 +1,7 @@
 use bevy::prelude::*;
-use std::fmt;
+use std::{f32::consts::TAU, fmt};
+
+use super::consts::CIRCLE_SEGMENTS;
 
 pub struct Triangle {
     pub a: Vec2,
@@ -103,3 +105,283 @@ pub fn are_triangles_colliding(tri1: &Triangle, tri2: &Triangle) -> bool {
     }
     true
 }
+
+/// How two shapes overlap
+#[derive(Clone, Copy, Debug)]
+pub struct Contact {
+    /// Unit vector pointing from the second shape towards the first, the
+    /// way the first has to move to get out
+    pub normal: Vec2,
+    /// How far the first has to move along `normal` to stop overlapping
+    pub depth: f32,
+}
+
+fn project(points: &[Vec2], axis: Vec2) -> (f32, f32) {
+    points
+        .iter()
+        .map(|p| p.dot(axis))
+        .fold((f32::MAX, f32::MIN), |(lo, hi), d| (lo.min(d), hi.max(d)))
+}
+
+fn centroid(points: &[Vec2]) -> Vec2 {
+    points.iter().copied().sum::<Vec2>() / points.len().max(1) as f32
+}
+
+/// Separating axis test between two convex polygons, given as their points
+/// in order. Touching doesn't count as overlapping.
+pub fn polygon_contact(poly1: &[Vec2], poly2: &[Vec2]) -> Option<Contact> {
+    let mut best: Option<Contact> = None;
+    for poly in [poly1, poly2] {
+        for (ix, a) in poly.iter().enumerate() {
+            let b = poly[(ix + 1) % poly.len()];
+            let edge = b - *a;
+            if edge.length_squared() <= f32::EPSILON {
+                continue;
+            }
+            let axis = edge.perp().normalize();
+            let (lo1, hi1) = project(poly1, axis);
+            let (lo2, hi2) = project(poly2, axis);
+            let depth = hi1.min(hi2) - lo1.max(lo2);
+            if depth <= 0.0 {
+                // Found a counterexample
+                return None;
+            }
+            if best.is_none_or(|c| depth < c.depth) {
+                best = Some(Contact {
+                    normal: axis,
+                    depth,
+                });
+            }
+        }
+    }
+    // Point the normal out of the second polygon
+    best.map(|mut contact| {
+        if (centroid(poly1) - centroid(poly2)).dot(contact.normal) < 0.0 {
+            contact.normal = -contact.normal;
+        }
+        contact
+    })
+}
+
+/// Whether the points, in order, make a convex polygon
+pub fn is_convex(points: &[Vec2]) -> bool {
+    if points.len() < 3 {
+        return false;
+    }
+    let mut sign = 0.0;
+    for (ix, a) in points.iter().enumerate() {
+        let b = points[(ix + 1) % points.len()];
+        let c = points[(ix + 2) % points.len()];
+        let cross = (b - *a).perp_dot(c - b);
+        if cross.abs() <= f32::EPSILON {
+            continue;
+        }
+        if sign * cross < 0.0 {
+            return false;
+        }
+        sign = cross.signum();
+    }
+    sign != 0.0
+}
+
+pub fn closest_point_on_segment(point: Vec2, segment: (Vec2, Vec2)) -> Vec2 {
+    let (a, b) = segment;
+    let edge = b - a;
+    let len2 = edge.length_squared();
+    if len2 <= f32::EPSILON {
+        return a;
+    }
+    a + edge * ((point - a).dot(edge) / len2).clamp(0.0, 1.0)
+}
+
+pub fn circle_contact(
+    center1: Vec2,
+    radius1: f32,
+    center2: Vec2,
+    radius2: f32,
+) -> Option<Contact> {
+    let diff = center1 - center2;
+    let dist = diff.length();
+    if dist >= radius1 + radius2 {
+        return None;
+    }
+    Some(Contact {
+        normal: diff.try_normalize().unwrap_or(Vec2::Y),
+        depth: radius1 + radius2 - dist,
+    })
+}
+
+/// How a circle overlaps a convex polygon, the normal pointing out of the
+/// polygon
+pub fn circle_polygon_contact(
+    center: Vec2,
+    radius: f32,
+    poly: &[Vec2],
+) -> Option<Contact> {
+    if poly.is_empty() {
+        return None;
+    }
+    let (closest, dist) = poly
+        .iter()
+        .enumerate()
+        .map(|(ix, a)| {
+            let b = poly[(ix + 1) % poly.len()];
+            let point = closest_point_on_segment(center, (*a, b));
+            (point, point.distance(center))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))?;
+    // Inside if it's on the same side of every edge
+    let mut crosses = poly.iter().enumerate().map(|(ix, a)| {
+        let b = poly[(ix + 1) % poly.len()];
+        (b - *a).perp_dot(center - *a)
+    });
+    let inside =
+        crosses.clone().all(|c| c >= 0.0) || crosses.all(|c| c <= 0.0);
+    if !inside && dist >= radius {
+        return None;
+    }
+    let outward = if dist <= f32::EPSILON {
+        // Right on the edge, so just go away from the middle
+        (center - centroid(poly)).try_normalize().unwrap_or(Vec2::Y)
+    } else if inside {
+        (closest - center) / dist
+    } else {
+        (center - closest) / dist
+    };
+    Some(Contact {
+        normal: outward,
+        depth: if inside { dist + radius } else { radius - dist },
+    })
+}
+
+/// A hitbox placed in the world, with its offset and its transform's
+/// translation, rotation and scale already applied
+#[derive(Clone, Debug)]
+pub enum WorldShape {
+    /// An unturned box
+    Aabb {
+        min: Vec2,
+        max: Vec2,
+    },
+    /// A convex polygon
+    Polygon(Vec<Vec2>),
+    Circle {
+        center: Vec2,
+        radius: f32,
+    },
+}
+impl WorldShape {
+    /// The corners of the box around the shape
+    pub fn bounds(&self) -> (Vec2, Vec2) {
+        match self {
+            WorldShape::Aabb { min, max } => (*min, *max),
+            WorldShape::Polygon(points) => points.iter().fold(
+                (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+                |(lo, hi), p| (lo.min(*p), hi.max(*p)),
+            ),
+            WorldShape::Circle { center, radius } => {
+                (*center - *radius, *center + *radius)
+            }
+        }
+    }
+
+    /// The corners of the shape, going around it. Circles get turned into
+    /// polygons with `CIRCLE_SEGMENTS` corners.
+    pub fn vertices(&self) -> Vec<Vec2> {
+        match self {
+            WorldShape::Aabb { min, max } => vec![
+                *min,
+                Vec2::new(max.x, min.y),
+                *max,
+                Vec2::new(min.x, max.y),
+            ],
+            WorldShape::Polygon(points) => points.clone(),
+            WorldShape::Circle { center, radius } => (0..CIRCLE_SEGMENTS)
+                .map(|ix| {
+                    let angle = ix as f32 / CIRCLE_SEGMENTS as f32 * TAU;
+                    *center + Vec2::new(angle.cos(), angle.sin()) * *radius
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether the box around this shape is a good stand in for it when
+    /// sweeping. Circles only stick out of theirs at the corners.
+    pub fn is_sweepable(&self) -> bool {
+        matches!(self, WorldShape::Aabb { .. } | WorldShape::Circle { .. })
+    }
+
+    /// Touching doesn't count as overlapping
+    pub fn overlaps(&self, other: &WorldShape) -> bool {
+        match (self, other) {
+            (
+                WorldShape::Aabb {
+                    min: min1,
+                    max: max1,
+                },
+                WorldShape::Aabb {
+                    min: min2,
+                    max: max2,
+                },
+            ) => {
+                !(min1.y >= max2.y
+                    || max1.y <= min2.y
+                    || max1.x <= min2.x
+                    || min1.x >= max2.x)
+            }
+            _ => self.contact(other).is_some(),
+        }
+    }
+
+    /// How this shape overlaps the other one, if it does
+    pub fn contact(&self, other: &WorldShape) -> Option<Contact> {
+        match (self, other) {
+            (
+                WorldShape::Circle {
+                    center: c1,
+                    radius: r1,
+                },
+                WorldShape::Circle {
+                    center: c2,
+                    radius: r2,
+                },
+            ) => circle_contact(*c1, *r1, *c2, *r2),
+            (WorldShape::Circle { center, radius }, _) => {
+                circle_polygon_contact(*center, *radius, &other.vertices())
+            }
+            (_, WorldShape::Circle { center, radius }) => {
+                circle_polygon_contact(*center, *radius, &self.vertices()).map(
+                    |contact| Contact {
+                        normal: -contact.normal,
+                        depth: contact.depth,
+                    },
+                )
+            }
+            (
+                WorldShape::Aabb {
+                    min: min1,
+                    max: max1,
+                },
+                WorldShape::Aabb {
+                    min: min2,
+                    max: max2,
+                },
+            ) => {
+                if !self.overlaps(other) {
+                    return None;
+                }
+                // Whichever way needs the smallest push
+                [
+                    (max1.x - min2.x, Vec2::NEG_X),
+                    (max2.x - min1.x, Vec2::X),
+                    (max2.y - min1.y, Vec2::Y),
+                    (max1.y - min2.y, Vec2::NEG_Y),
+                ]
+                .into_iter()
+                .min_by(|(a, _), (b, _)| a.total_cmp(b))
+                .map(|(depth, normal)| Contact { normal, depth })
+            }
+            _ => polygon_contact(&self.vertices(), &other.vertices()),
+        }
+    }
+}
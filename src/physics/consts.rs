@@ -1,11 +1,58 @@
-use bevy::ecs::component::Component;
+use bevy::{ecs::component::Component, math::Vec2};
 
 pub const GRAVITY: f32 = 980.0;
 pub const COLLISION_THRESHOLD: f32 = 0.001;
-#[derive(Component, PartialEq)]
+/// How close to the top of a moving platform something has to be to ride it
+pub const CARRY_THRESHOLD: f32 = 1.0;
+/// Fraction of the speed along a surface lost each frame on it, unless the
+/// surface says otherwise
+pub const DEFAULT_FRICTION: f32 = 0.18;
+/// How much speed is kept bouncing off a surface, unless it says otherwise
+pub const DEFAULT_RESTITUTION: f32 = 0.4;
+/// How far into a block a swept move stops, so the collision still resolves
+pub const SWEEP_SKIN: f32 = 0.5;
+/// How far apart two block faces can be and still count as one surface
+pub const FLUSH_TOLERANCE: f32 = 0.01;
+/// How far below the top of a one-way platform something can have been
+/// last frame and still land on it
+pub const ONE_WAY_TOLERANCE: f32 = 2.0;
+/// How many times a move can hit something and slide along it in a frame
+pub const MAX_SWEEPS: usize = 3;
+/// Side length of a broadphase grid cell
+pub const BROADPHASE_CELL_SIZE: f32 = 100.0;
+/// How many corners a circle gets when it has to be a polygon
+pub const CIRCLE_SEGMENTS: usize = 16;
+/// The layer blocks and everything else in a level are on by default
+pub const LAYER_WORLD: u32 = 1 << 0;
+/// The layer agents are on by default
+pub const LAYER_AGENT: u32 = 1 << 1;
+/// The layer eyes are on. Leave it out of a hitbox's mask to make it
+/// see-through.
+pub const LAYER_SIGHT: u32 = 1 << 2;
+pub const LAYER_ALL: u32 = u32::MAX;
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Dir {
     Left,
     Right,
     Up,
     Down,
 }
+impl Dir {
+    pub fn vector(&self) -> Vec2 {
+        match self {
+            Dir::Left => Vec2::NEG_X,
+            Dir::Right => Vec2::X,
+            Dir::Up => Vec2::Y,
+            Dir::Down => Vec2::NEG_Y,
+        }
+    }
+
+    pub fn flipped(&self) -> Dir {
+        match self {
+            Dir::Left => Dir::Right,
+            Dir::Right => Dir::Left,
+            Dir::Up => Dir::Down,
+            Dir::Down => Dir::Up,
+        }
+    }
+}
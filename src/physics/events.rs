@@ -0,0 +1,144 @@
+//! Lets the rest of the game react to what physics did, instead of every
+//! system scanning for overlaps on its own
+
+use bevy::{
+    prelude::*, render::texture::DEFAULT_IMAGE_HANDLE, utils::HashSet,
+};
+
+use super::{
+    are_colliding, broadphase::SpatialHash, physics_collide, Hitbox, Moveable,
+    Shape,
+};
+
+/// A hitbox that never pushes anything, and only reports what moveable
+/// things are inside of it
+#[derive(Component, Default)]
+pub struct Sensor {
+    /// Everything that was inside as of the last `physics_triggers`
+    pub overlapping: HashSet<Entity>,
+}
+
+/// A tinted box that things pass through instead of landing on, for goals,
+/// pickups and the like to build on
+#[derive(Bundle)]
+pub struct SensorBundle {
+    spatial: SpatialBundle,
+    sprite: Sprite,
+    texture: Handle<Image>,
+    hitbox: Hitbox,
+    sensor: Sensor,
+}
+impl SensorBundle {
+    pub fn new(pos: Vec2, size: Vec2, color: Color) -> SensorBundle {
+        SensorBundle {
+            spatial: SpatialBundle {
+                transform: Transform {
+                    translation: pos.extend(0.0),
+                    ..default()
+                },
+                ..default()
+            },
+            sprite: Sprite {
+                color,
+                custom_size: Some(size),
+                ..default()
+            },
+            texture: DEFAULT_IMAGE_HANDLE.typed(),
+            hitbox: Hitbox {
+                pos: Vec2::ZERO,
+                size,
+                shape: Shape::Rect,
+                ..default()
+            },
+            sensor: Sensor::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TriggerPhase {
+    /// It just came in
+    Enter,
+    /// It was already in, and still is
+    Stay,
+    /// It just left, or stopped existing
+    Exit,
+}
+
+#[derive(Event, Clone, Copy, Debug)]
+pub struct TriggerEvent {
+    pub sensor: Entity,
+    pub other: Entity,
+    pub phase: TriggerPhase,
+}
+impl TriggerEvent {
+    /// Whether `other` is inside the sensor right now
+    pub fn is_inside(&self) -> bool {
+        self.phase != TriggerPhase::Exit
+    }
+}
+
+/// Sent whenever `physics_collide` pushes two things apart
+#[derive(Event, Clone, Copy, Debug)]
+pub struct CollisionEvent {
+    /// Always the moveable one
+    pub a: Entity,
+    pub b: Entity,
+    /// The way `a` got pushed
+    pub normal: Vec2,
+    /// How much speed times mass it took to stop them moving into each
+    /// other, zero if they were already moving apart
+    pub impulse: f32,
+}
+
+/// Checks every sensor against the moveable things, once they're done
+/// moving for the frame
+pub fn physics_triggers(
+    mut sensors: Query<(Entity, &Hitbox, &Transform, &mut Sensor)>,
+    movers: Query<
+        (Entity, &Hitbox, &Transform),
+        (With<Moveable>, Without<Sensor>),
+    >,
+    mut events: EventWriter<TriggerEvent>,
+) {
+    let mut grid = SpatialHash::default();
+    for (entity, hitbox, transform) in movers.iter() {
+        grid.insert_hitbox(entity, hitbox, transform);
+    }
+    for (sensor, s_hb, s_t, mut s) in sensors.iter_mut() {
+        let inside: HashSet<Entity> = movers
+            .iter_many(grid.query_hitbox(s_hb, s_t))
+            .filter(|(_, hb, t)| {
+                hb.layers.interacts(&s_hb.layers)
+                    && are_colliding(hb, t, s_hb, s_t)
+            })
+            .map(|(entity, ..)| entity)
+            .collect();
+        for &other in inside.iter() {
+            let phase = if s.overlapping.contains(&other) {
+                TriggerPhase::Stay
+            } else {
+                TriggerPhase::Enter
+            };
+            events.send(TriggerEvent {
+                sensor,
+                other,
+                phase,
+            });
+        }
+        for &other in s.overlapping.difference(&inside) {
+            events.send(TriggerEvent {
+                sensor,
+                other,
+                phase: TriggerPhase::Exit,
+            });
+        }
+        s.overlapping = inside;
+    }
+}
+
+pub fn register_physics_events(app: &mut App) {
+    app.add_event::<TriggerEvent>()
+        .add_event::<CollisionEvent>()
+        .add_systems(Update, physics_triggers.after(physics_collide));
+}
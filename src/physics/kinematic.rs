@@ -0,0 +1,140 @@
+//! Bodies that move along a set path no matter what hits them, like moving
+//! platforms. They don't have `Moveable`, so to everything else they're
+//! immovable, just with a velocity.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    consts::{Dir, CARRY_THRESHOLD},
+    get_bounds, Hitbox, Moveable, Velocity,
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum PlatformPath {
+    /// Back and forth between two points
+    PingPong { a: Vec2, b: Vec2 },
+    /// Around the points in order, then back to the first
+    Waypoints(Vec<Vec2>),
+}
+impl PlatformPath {
+    pub fn start(&self) -> Vec2 {
+        match self {
+            PlatformPath::PingPong { a, .. } => *a,
+            PlatformPath::Waypoints(points) => {
+                points.first().copied().unwrap_or(Vec2::ZERO)
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Kinematic {
+    points: Vec<Vec2>,
+    looped: bool,
+    speed: f32,
+    /// Index of the point we're heading to
+    target: usize,
+    /// Which way through `points` we're going, only changes for ping pong
+    forward: bool,
+}
+impl Kinematic {
+    pub fn new(path: &PlatformPath, speed: f32) -> Kinematic {
+        let (points, looped) = match path {
+            PlatformPath::PingPong { a, b } => (vec![*a, *b], false),
+            PlatformPath::Waypoints(points) => (points.clone(), true),
+        };
+        Kinematic {
+            points,
+            looped,
+            speed,
+            target: 1,
+            forward: true,
+        }
+    }
+
+    fn advance_target(&mut self) {
+        let n = self.points.len();
+        if self.looped {
+            self.target = (self.target + 1) % n;
+            return;
+        }
+        if self.forward && self.target + 1 >= n {
+            self.forward = false;
+        } else if !self.forward && self.target == 0 {
+            self.forward = true;
+        }
+        self.target = if self.forward {
+            self.target + 1
+        } else {
+            self.target - 1
+        };
+    }
+
+    /// Where the body ends up after travelling `speed * dt` along the path
+    pub fn step(&mut self, mut pos: Vec2, dt: f32) -> Vec2 {
+        if self.points.len() < 2 {
+            return pos;
+        }
+        let mut remaining = self.speed * dt;
+        // Bounded so a path of identical points can't spin forever
+        for _ in 0..self.points.len() * 2 {
+            if remaining <= 0.0 {
+                break;
+            }
+            let to_target = self.points[self.target] - pos;
+            let dist = to_target.length();
+            if dist > remaining {
+                pos += to_target / dist * remaining;
+                break;
+            }
+            pos = self.points[self.target];
+            remaining -= dist;
+            self.advance_target();
+        }
+        pos
+    }
+}
+
+/// Moves kinematic bodies along their paths, carrying along anything
+/// standing on them
+pub fn physics_kinematic(
+    time: Res<Time>,
+    mut q_kinematic: Query<
+        (&mut Kinematic, &mut Transform, &mut Velocity, &Hitbox),
+        Without<Moveable>,
+    >,
+    mut q_movable: Query<(&mut Transform, &Hitbox, &Moveable)>,
+) {
+    let dt = time.delta_seconds();
+    if dt <= 0.0 {
+        return;
+    }
+    for (mut kinematic, mut k_t, mut k_v, k_hb) in q_kinematic.iter_mut() {
+        let (kl, kr, kt, kb) = get_bounds(k_hb, &k_t);
+        let old_pos = k_t.translation.truncate();
+        let new_pos = kinematic.step(old_pos, dt);
+        let delta = new_pos - old_pos;
+        k_t.translation = new_pos.extend(k_t.translation.z);
+        k_v.x = delta.x / dt;
+        k_v.y = delta.y / dt;
+
+        for (mut m_t, m_hb, moveable) in q_movable.iter_mut() {
+            if !m_hb.layers.interacts(&k_hb.layers) {
+                continue;
+            }
+            let (ml, mr, mt, mb) = get_bounds(m_hb, &m_t);
+            // Things with flipped gravity stand on the underside
+            let gap = if moveable.gravity_dir == Dir::Up {
+                mt - kb
+            } else {
+                mb - kt
+            };
+            let standing_on =
+                gap.abs() <= CARRY_THRESHOLD && mr > kl && ml < kr;
+            if standing_on {
+                m_t.translation += delta.extend(0.0);
+            }
+        }
+    }
+}
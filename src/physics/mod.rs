@@ -1,75 +1,161 @@
+pub mod broadphase;
 pub mod collisions;
 pub mod consts;
+pub mod events;
+pub mod kinematic;
 
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::physics::consts::COLLISION_THRESHOLD;
+use crate::physics::consts::{
+    COLLISION_THRESHOLD, DEFAULT_FRICTION, DEFAULT_RESTITUTION,
+    FLUSH_TOLERANCE, LAYER_ALL, LAYER_WORLD, MAX_SWEEPS, ONE_WAY_TOLERANCE,
+    SWEEP_SKIN,
+};
 
-use self::{collisions::Triangle, consts::GRAVITY};
+use self::{
+    broadphase::{physics_broadphase, Broadphase, SpatialHash},
+    collisions::{Contact, Triangle, WorldShape},
+    consts::{Dir, GRAVITY},
+    events::{register_physics_events, CollisionEvent, Sensor},
+    kinematic::physics_kinematic,
+};
+
+/// The shape of a hitbox
+#[derive(Clone, Debug, Default)]
+pub enum Shape {
+    /// A box of the hitbox's size, turned along with the transform
+    #[default]
+    Rect,
+    /// A convex polygon, points relative to the hitbox's position
+    Polygon(Vec<Vec2>),
+    /// Centered on the hitbox's position
+    Circle { radius: f32 },
+}
+
+/// Which layers a hitbox is on, and which layers it bumps into. Two
+/// hitboxes only touch if each one's mask has a layer the other is on.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CollisionLayers {
+    pub layer: u32,
+    pub mask: u32,
+}
+impl Default for CollisionLayers {
+    fn default() -> CollisionLayers {
+        CollisionLayers {
+            layer: LAYER_WORLD,
+            mask: LAYER_ALL,
+        }
+    }
+}
+impl CollisionLayers {
+    pub fn interacts(&self, other: &CollisionLayers) -> bool {
+        self.mask & other.layer != 0 && other.mask & self.layer != 0
+    }
+}
 
 /// A simple hitbox
-/// Position is relative to the parent's transform
-/// TODO? Implement get_bounds(transform: &Transform) that returns left/right/top/bot
-/// for a hitbox on a specific transform
-#[derive(Component)]
+/// Position and size are in the transform's local space, so they get moved,
+/// turned and scaled along with it
+#[derive(Component, Default)]
 pub struct Hitbox {
     pub pos: Vec2,
     pub size: Vec2,
+    pub shape: Shape,
+    pub layers: CollisionLayers,
 }
 impl Hitbox {
-    pub fn two_triangles(&self, t: &Transform) -> (Triangle, Triangle) {
-        let trans = t.translation;
-        (
-            Triangle {
-                a: Vec2 {
-                    x: trans.x + self.size.x / 2.0,
-                    y: trans.y + self.size.y / 2.0,
-                },
-                b: Vec2 {
-                    x: trans.x - self.size.x / 2.0,
-                    y: trans.y + self.size.y / 2.0,
-                },
-                c: Vec2 {
-                    x: trans.x - self.size.x / 2.0,
-                    y: trans.y - self.size.y / 2.0,
-                },
+    /// A convex polygon hitbox, its size being the box around the points
+    pub fn polygon(points: Vec<Vec2>) -> Hitbox {
+        let (lo, hi) = points.iter().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(lo, hi), p| (lo.min(*p), hi.max(*p)),
+        );
+        Hitbox {
+            pos: Vec2 { x: 0.0, y: 0.0 },
+            size: if points.is_empty() {
+                Vec2::ZERO
+            } else {
+                hi - lo
             },
-            Triangle {
-                a: Vec2 {
-                    x: trans.x + self.size.x / 2.0,
-                    y: trans.y + self.size.y / 2.0,
-                },
-                b: Vec2 {
-                    x: trans.x - self.size.x / 2.0,
-                    y: trans.y - self.size.y / 2.0,
-                },
-                c: Vec2 {
-                    x: trans.x + self.size.x / 2.0,
-                    y: trans.y - self.size.y / 2.0,
-                },
+            shape: Shape::Polygon(points),
+            ..default()
+        }
+    }
+
+    pub fn circle(radius: f32) -> Hitbox {
+        Hitbox {
+            pos: Vec2 { x: 0.0, y: 0.0 },
+            size: Vec2::splat(radius * 2.0),
+            shape: Shape::Circle { radius },
+            ..default()
+        }
+    }
+
+    /// Where the hitbox actually is. Everything that needs a hitbox's
+    /// geometry goes through this, so they all agree.
+    pub fn world(&self, t: &Transform) -> WorldShape {
+        let scale = t.scale.truncate();
+        let to_world = |p: Vec2| {
+            t.translation.truncate()
+                + (t.rotation * (p * scale).extend(0.0)).truncate()
+        };
+        match &self.shape {
+            Shape::Rect => {
+                let half = self.size / 2.0;
+                let corners: Vec<Vec2> = [
+                    Vec2::new(-half.x, -half.y),
+                    Vec2::new(half.x, -half.y),
+                    Vec2::new(half.x, half.y),
+                    Vec2::new(-half.x, half.y),
+                ]
+                .into_iter()
+                .map(|corner| to_world(self.pos + corner))
+                .collect();
+                if t.rotation.abs_diff_eq(Quat::IDENTITY, 1e-6) {
+                    // Scale can flip it, so the corners may be in any order
+                    WorldShape::Aabb {
+                        min: corners[0].min(corners[2]),
+                        max: corners[0].max(corners[2]),
+                    }
+                } else {
+                    WorldShape::Polygon(corners)
+                }
+            }
+            Shape::Polygon(points) => WorldShape::Polygon(
+                points.iter().map(|p| to_world(self.pos + *p)).collect(),
+            ),
+            // Stretching would make an ellipse, so it grows to fit instead
+            Shape::Circle { radius } => WorldShape::Circle {
+                center: to_world(self.pos),
+                radius: radius * scale.abs().max_element(),
             },
-        )
+        }
     }
 
+    /// The corners of the hitbox in world space
+    pub fn vertices(&self, t: &Transform) -> Vec<Vec2> {
+        self.world(t).vertices()
+    }
+
+    /// The hitbox split up into a fan of triangles
+    pub fn triangles(&self, t: &Transform) -> Vec<Triangle> {
+        let points = self.vertices(t);
+        (1..points.len().saturating_sub(1))
+            .map(|ix| Triangle {
+                a: points[0],
+                b: points[ix],
+                c: points[ix + 1],
+            })
+            .collect()
+    }
+
+    /// The edges of the hitbox in world space
     pub fn segments(&self, t: &Transform) -> Vec<(Vec2, Vec2)> {
-        let trans = t.translation;
-        let a = Vec2 {
-            x: trans.x - self.size.x / 2.0,
-            y: trans.y - self.size.y / 2.0,
-        };
-        let b = Vec2 {
-            x: trans.x - self.size.x / 2.0,
-            y: trans.y + self.size.y / 2.0,
-        };
-        let c = Vec2 {
-            x: trans.x + self.size.x / 2.0,
-            y: trans.y + self.size.y / 2.0,
-        };
-        let d = Vec2 {
-            x: trans.x + self.size.x / 2.0,
-            y: trans.y - self.size.y / 2.0,
-        };
-        vec![(a, b), (b, c), (c, d), (d, a)]
+        let points = self.vertices(t);
+        (0..points.len())
+            .map(|ix| (points[ix], points[(ix + 1) % points.len()]))
+            .collect()
     }
 }
 
@@ -91,47 +177,261 @@ pub struct Velocity {
 #[derive(Component)]
 pub struct Moveable {
     pub gravity_enabled: bool,
+    /// How much of the usual gravity it falls with
+    pub gravity_scale: f32,
+    /// Which way it falls. Agents only expect `Up` or `Down`.
+    pub gravity_dir: Dir,
+    /// Only matters when bumping into other moveable things
+    pub mass: f32,
+    /// How much of its speed is kept when bouncing off another moveable.
+    /// Immoveable things bounce it by their `PhysicsMaterial` instead.
+    pub restitution: f32,
+}
+
+/// How moveable things bounce and slide off of something immoveable
+#[derive(Component, Clone, Copy, Debug)]
+pub struct PhysicsMaterial {
+    /// Fraction of the speed along the surface lost each frame on it
+    pub friction: f32,
+    /// Fraction of the speed into the surface kept when bouncing off it
+    pub restitution: f32,
 }
+impl Default for PhysicsMaterial {
+    fn default() -> PhysicsMaterial {
+        PhysicsMaterial {
+            friction: DEFAULT_FRICTION,
+            restitution: DEFAULT_RESTITUTION,
+        }
+    }
+}
+
+/// Makes an immoveable thing only solid from above, so things can jump up
+/// through it and land on top
+#[derive(Component)]
+pub struct OneWay;
+
+/// Where a moveable thing was before it last moved
+#[derive(Component, Default)]
+pub struct PrevPosition(pub Vec2);
+
+/// Lets a moveable thing fall through one-way platforms while set
+#[derive(Component, Default)]
+pub struct DropThrough(pub bool);
 
 pub fn physics_setup() {}
 
+/// How long the frame was, capped so a long hitch doesn't fling things
+pub fn physics_delta(time: &Time) -> f32 {
+    const SUPPOSED_SPF: f32 = 1.0 / 60.0;
+    time.delta_seconds().min(SUPPOSED_SPF * 3.0)
+}
+
 pub fn physics_gravity(
     time: Res<Time>,
     mut query: Query<(&mut Velocity, &Moveable)>,
 ) {
-    const SUPPOSED_SPF: f32 = 1.0 / 60.0;
-    let mut adjust_mult = time.delta_seconds();
-    if adjust_mult > SUPPOSED_SPF * 3.0 {
-        adjust_mult = SUPPOSED_SPF * 3.0;
-    }
+    let adjust_mult = physics_delta(&time);
     for (mut velocity, moveable) in query.iter_mut() {
         if !moveable.gravity_enabled {
             // Ignore agents where gravity is turned off
             continue;
         }
-        velocity.y -= GRAVITY * adjust_mult;
+        let pull = moveable.gravity_dir.vector()
+            * GRAVITY
+            * moveable.gravity_scale
+            * adjust_mult;
+        velocity.x += pull.x;
+        velocity.y += pull.y;
+    }
+}
+
+/// When a moveable thing moving by `delta` would first touch the other
+/// hitbox, as a fraction of `delta`, along with the normal of the face it
+/// hits. Things that already overlap are left to `physics_collide`, and so
+/// are turned or polygon hitboxes, since their boxes aren't their shape.
+/// Circles can get stopped a little early next to corners.
+pub fn sweep(
+    h1: &Hitbox,
+    t1: &Transform,
+    delta: Vec2,
+    h2: &Hitbox,
+    t2: &Transform,
+) -> Option<(f32, Vec2)> {
+    let (s1, s2) = (h1.world(t1), h2.world(t2));
+    if !(s1.is_sweepable() && matches!(s2, WorldShape::Aabb { .. }))
+        || s1.overlaps(&s2)
+    {
+        return None;
+    }
+    let (min1, max1) = s1.bounds();
+    let (min2, max2) = s2.bounds();
+    // When the boxes start and stop overlapping along a single axis
+    let axis_times = |d: f32, lo1: f32, hi1: f32, lo2: f32, hi2: f32| {
+        if d > 0.0 {
+            Some(((lo2 - hi1) / d, (hi2 - lo1) / d))
+        } else if d < 0.0 {
+            Some(((hi2 - lo1) / d, (lo2 - hi1) / d))
+        } else if hi1 > lo2 && lo1 < hi2 {
+            Some((f32::NEG_INFINITY, f32::INFINITY))
+        } else {
+            None
+        }
+    };
+    let (x_entry, x_exit) =
+        axis_times(delta.x, min1.x, max1.x, min2.x, max2.x)?;
+    let (y_entry, y_exit) =
+        axis_times(delta.y, min1.y, max1.y, min2.y, max2.y)?;
+    let entry = x_entry.max(y_entry);
+    let exit = x_exit.min(y_exit);
+    if entry >= exit || !(0.0..1.0).contains(&entry) {
+        return None;
+    }
+    let normal = if x_entry > y_entry {
+        Vec2::new(-delta.x.signum(), 0.0)
+    } else {
+        Vec2::new(0.0, -delta.y.signum())
+    };
+    Some((entry, normal))
+}
+
+/// Whether `other` sits flush against the face of `block` facing `normal`
+/// everywhere `mover` touches that face, so the face is a seam between two
+/// blocks rather than an edge to catch on
+fn covers_face(
+    block: (Vec2, Vec2),
+    normal: Vec2,
+    mover: (Vec2, Vec2),
+    other: (Vec2, Vec2),
+) -> bool {
+    // Swept normals are always along an axis
+    let (along, across) = if normal.x != 0.0 {
+        (Vec2::X, Vec2::Y)
+    } else {
+        (Vec2::Y, Vec2::X)
+    };
+    let (face, other_face) = if normal.dot(along) < 0.0 {
+        (block.0.dot(along), other.1.dot(along))
+    } else {
+        (block.1.dot(along), other.0.dot(along))
+    };
+    let lo = mover.0.dot(across).max(block.0.dot(across));
+    let hi = mover.1.dot(across).min(block.1.dot(across));
+    (face - other_face).abs() < FLUSH_TOLERANCE
+        && other.0.dot(across) <= lo + FLUSH_TOLERANCE
+        && other.1.dot(across) >= hi - FLUSH_TOLERANCE
+}
+
+/// Moves by `delta`, stopping at the first of `blocks` in the way and
+/// sliding along it. Each block comes with whether it's one-way.
+fn sweep_move(
+    hitbox: &Hitbox,
+    transform: &mut Transform,
+    delta: Vec2,
+    blocks: &[(&Hitbox, &Transform, bool)],
+    up: Vec2,
+    dropping: bool,
+) {
+    let solids: Vec<(Vec2, Vec2)> = blocks
+        .iter()
+        .filter(|(.., one_way)| !one_way)
+        .filter_map(|(h2, t2, _)| match h2.world(t2) {
+            WorldShape::Aabb { min, max } => Some((min, max)),
+            _ => None,
+        })
+        .collect();
+    let mut remaining = delta;
+    for _ in 0..MAX_SWEEPS {
+        let (min, max) = hitbox.world(transform).bounds();
+        let hit = blocks
+            .iter()
+            .filter_map(|(h2, t2, one_way)| {
+                let (toi, normal) =
+                    sweep(hitbox, transform, remaining, h2, t2)?;
+                // One-way platforms only stop things coming down on top,
+                // whichever way down is for them
+                let stops = !one_way || (!dropping && normal.dot(up) > 0.0);
+                // Running along a floor made of several blocks shouldn't
+                // catch on the sides where they meet
+                let at = remaining * toi;
+                let seam = solids.iter().any(|other| {
+                    covers_face(
+                        h2.world(t2).bounds(),
+                        normal,
+                        (min + at, max + at),
+                        *other,
+                    )
+                });
+                (stops && !seam).then_some((toi, normal))
+            })
+            .min_by(|(a, _), (b, _)| a.total_cmp(b));
+        let Some((toi, normal)) = hit else {
+            transform.translation += remaining.extend(0.0);
+            break;
+        };
+        transform.translation += (remaining * toi).extend(0.0);
+        remaining *= 1.0 - toi;
+        // Sink in a little so the collision gets resolved, then slide
+        // along the face with whatever movement is left
+        let into = -remaining.dot(normal);
+        transform.translation -= (normal * into.min(SWEEP_SKIN)).extend(0.0);
+        remaining += normal * into;
     }
 }
 
+/// Moves moveable things by their velocity, stopping them at the first
+/// immoveable thing in their way so fast things can't pass through thin
+/// blocks. They're stopped just inside it so `physics_collide` can bounce
+/// them off it like normal.
 pub fn physics_move(
     time: Res<Time>,
-    mut query: Query<(&Velocity, &mut Transform), With<Moveable>>,
+    mut query: Query<(
+        &Hitbox,
+        &Velocity,
+        &mut Transform,
+        &Moveable,
+        Option<&mut PrevPosition>,
+        Option<&DropThrough>,
+    )>,
+    q_immovable: Query<
+        (&Hitbox, &Transform, Option<&OneWay>),
+        Without<Moveable>,
+    >,
+    broadphase: Res<Broadphase>,
 ) {
-    for (velocity, mut transform) in query.iter_mut() {
-        transform.translation.x += velocity.x * time.delta_seconds();
-        transform.translation.y += velocity.y * time.delta_seconds();
+    for (hitbox, velocity, mut transform, moveable, prev, drop) in
+        query.iter_mut()
+    {
+        if let Some(mut prev) = prev {
+            prev.0 = transform.translation.truncate();
+        }
+        let dropping = drop.is_some_and(|d| d.0);
+        let up = -moveable.gravity_dir.vector();
+        let delta = Vec2 {
+            x: velocity.x,
+            y: velocity.y,
+        } * time.delta_seconds();
+        // Everything the whole move could possibly touch
+        let (left, right, top, bot) = get_bounds(hitbox, &transform);
+        let candidates = broadphase.immovable.query(
+            Vec2::new(left, bot) + delta.min(Vec2::ZERO),
+            Vec2::new(right, top) + delta.max(Vec2::ZERO),
+        );
+        let blocks: Vec<_> = q_immovable
+            .iter_many(&candidates)
+            .filter(|(h2, ..)| hitbox.layers.interacts(&h2.layers))
+            .map(|(h2, t2, one_way)| (h2, t2, one_way.is_some()))
+            .collect();
+        sweep_move(hitbox, &mut transform, delta, &blocks, up, dropping);
     }
 }
 
+/// The box around the hitbox, as left/right/top/bot
 pub fn get_bounds(
     hitbox: &Hitbox,
     transform: &Transform,
 ) -> (f32, f32, f32, f32) {
-    let left = transform.translation.x + hitbox.pos.x - hitbox.size.x / 2.0;
-    let right = left + hitbox.size.x;
-    let top = transform.translation.y + hitbox.pos.y + hitbox.size.y / 2.0;
-    let bot = top - hitbox.size.y;
-    (left, right, top, bot)
+    let (min, max) = hitbox.world(transform).bounds();
+    (min.x, max.x, max.y, min.y)
 }
 
 pub fn are_colliding(
@@ -140,73 +440,95 @@ pub fn are_colliding(
     h2: &Hitbox,
     t2: &Transform,
 ) -> bool {
-    let left1 = t1.translation.x + h1.pos.x - h1.size.x / 2.0;
-    let right1 = left1 + h1.size.x;
-    let top1 = t1.translation.y + h1.pos.y + h1.size.y / 2.0;
-    let bot1 = top1 - h1.size.y;
-
-    let left2 = t2.translation.x + h2.pos.x - h2.size.x / 2.0;
-    let right2 = left2 + h2.size.x;
-    let top2 = t2.translation.y + h2.pos.y + h2.size.y / 2.0;
-    let bot2 = top2 - h2.size.y;
-
-    return !(bot1 >= top2
-        || top1 <= bot2
-        || right1 <= left2
-        || left1 >= right2);
+    h1.world(t1).overlaps(&h2.world(t2))
 }
 
-pub fn resolve_move_immove_collision(
+/// How the first hitbox overlaps the second, if it does
+pub fn hitbox_contact(
     h1: &Hitbox,
-    t1: &mut Transform,
-    v1: &mut Velocity,
+    t1: &Transform,
     h2: &Hitbox,
     t2: &Transform,
-) {
-    // Get the bounds
-    let (ml, mr, mt, mb) = get_bounds(h1, t1);
-    let (il, ir, it, ib) = get_bounds(h2, t2);
-    const INF: f32 = 99999.9;
-
-    // WHY RUST WHY NO CMP FOR F32???
-    let left_resolve = if mr > il { mr - il } else { INF };
-    let mut min_resolve = left_resolve;
-    let right_resolve = if ml < ir { ir - ml } else { INF };
-    if right_resolve < min_resolve {
-        min_resolve = right_resolve;
-    }
-    let top_resolve = if mb < it { it - mb } else { INF };
-    if top_resolve < min_resolve {
-        min_resolve = top_resolve;
-    }
-    let bot_resolve = if mt > ib { mt - ib } else { INF };
-    if bot_resolve < min_resolve {
-        min_resolve = bot_resolve;
-    }
-
-    // Resolve appropriately
-    if left_resolve <= min_resolve {
-        t1.translation.x -= left_resolve;
-        if v1.x > 0.0 {
-            v1.x *= -1.0 * 0.4;
-        }
-    } else if right_resolve <= min_resolve {
-        t1.translation.x += right_resolve;
-        if v1.x < 0.0 {
-            v1.x *= -1.0 * 0.4;
-        }
-    } else if top_resolve <= min_resolve {
-        t1.translation.y += top_resolve;
-        if v1.y < 0.0 {
-            // Only flip velocity if would move further into this thing
-            v1.y *= -1.0 * 0.4;
-        }
-    } else {
-        t1.translation.y -= bot_resolve;
-        if v1.y > 0.0 {
-            // Only flip velocity if would move further into this thing
-            v1.y *= -1.0 * 0.4;
-        }
+) -> Option<Contact> {
+    h1.world(t1).contact(&h2.world(t2))
+}
+
+/// One side of a collision that can be pushed around
+pub struct MovingBody<'a> {
+    pub hitbox: &'a Hitbox,
+    pub transform: &'a mut Transform,
+    pub velocity: &'a mut Velocity,
+    pub moveable: &'a Moveable,
+    /// Where it was before it last moved
+    pub prev: Vec2,
+}
+
+/// One side of a collision that stays put no matter what hits it
+pub struct FixedBody<'a> {
+    pub hitbox: &'a Hitbox,
+    pub transform: &'a Transform,
+    pub velocity: &'a Velocity,
+    pub material: &'a PhysicsMaterial,
+    pub one_way: bool,
+}
+
+/// Whether the body is coming down onto the top of a one-way platform,
+/// rather than passing up or sideways through it. Down is whichever way
+/// the body falls.
+fn lands_on_one_way(
+    body: &MovingBody,
+    relative: Vec2,
+    normal: Vec2,
+    platform: &FixedBody,
+) -> bool {
+    let up = -body.moveable.gravity_dir.vector();
+    let was = Transform {
+        translation: body.prev.extend(body.transform.translation.z),
+        ..*body.transform
+    };
+    // Gravity only goes along an axis, so the corners of the boxes are as
+    // far as they go along it
+    let (min1, max1) = body.hitbox.world(&was).bounds();
+    let (min2, max2) = platform.hitbox.world(platform.transform).bounds();
+    let was_feet = min1.dot(up).min(max1.dot(up));
+    let top = min2.dot(up).max(max2.dot(up));
+    normal.dot(up) > 0.0
+        && relative.dot(up) <= 0.0
+        && was_feet >= top - ONE_WAY_TOLERANCE
+}
+
+/// Pushes the moveable thing out of the immoveable one, bouncing and
+/// sliding it off relative to however fast the immoveable thing is going.
+/// If the immoveable thing is one-way, it's only pushed out when landing
+/// on top, judging by its velocity and where it was before it moved.
+/// Returns the way it got pushed and the impulse it took, if they touched.
+pub fn resolve_move_immove_collision(
+    body: MovingBody,
+    fixed: FixedBody,
+) -> Option<(Vec2, f32)> {
+    let Contact { normal, depth } = hitbox_contact(
+        body.hitbox,
+        body.transform,
+        fixed.hitbox,
+        fixed.transform,
+    )?;
+    let (v1, v2) = (&body.velocity, fixed.velocity);
+    let relative = Vec2::new(v1.x - v2.x, v1.y - v2.y);
+    if fixed.one_way && !lands_on_one_way(&body, relative, normal, &fixed) {
+        return None;
+    }
+    let material = fixed.material;
+    body.transform.translation += (normal * depth).extend(0.0);
+    let v1 = body.velocity;
+    let closing = relative.dot(normal);
+    if closing < 0.0 {
+        // Only flip velocity if would move further into this thing, and
+        // only rub against it while pressed into it
+        let along = relative - normal * closing;
+        let change = -normal * (1.0 + material.restitution) * closing
+            - along * material.friction;
+        v1.x += change.x;
+        v1.y += change.y;
     }
     if v1.x.abs() < COLLISION_THRESHOLD {
         v1.x = 0.0;
@@ -214,30 +536,506 @@ pub fn resolve_move_immove_collision(
     if v1.y.abs() < COLLISION_THRESHOLD {
         v1.y = 0.0;
     }
+    let impulse =
+        -(1.0 + material.restitution) * closing.min(0.0) * body.moveable.mass;
+    Some((normal, impulse))
+}
+
+/// Pushes two moveable things out of each other, splitting the push by
+/// mass, and bounces them off each other if they're moving closer.
+/// Returns the way 1 got pushed and the impulse it took, if they touched.
+pub fn resolve_move_move_collision(
+    b1: MovingBody,
+    b2: MovingBody,
+) -> Option<(Vec2, f32)> {
+    let MovingBody {
+        hitbox: h1,
+        transform: t1,
+        velocity: v1,
+        moveable: m1,
+        ..
+    } = b1;
+    let MovingBody {
+        hitbox: h2,
+        transform: t2,
+        velocity: v2,
+        moveable: m2,
+        ..
+    } = b2;
+    let Contact { normal, depth } = hitbox_contact(h1, t1, h2, t2)?;
+
+    // Zero (or less) mass counts as infinitely heavy, but if both are we
+    // still have to push them apart somehow
+    let inv1 = if m1.mass > 0.0 { 1.0 / m1.mass } else { 0.0 };
+    let inv2 = if m2.mass > 0.0 { 1.0 / m2.mass } else { 0.0 };
+    let (inv1, inv2) = if inv1 + inv2 > 0.0 {
+        (inv1, inv2)
+    } else {
+        (1.0, 1.0)
+    };
+    let total = inv1 + inv2;
+    let restitution = m1.restitution.min(m2.restitution);
+
+    t1.translation += (normal * depth * inv1 / total).extend(0.0);
+    t2.translation -= (normal * depth * inv2 / total).extend(0.0);
+    let closing = (v1.x - v2.x) * normal.x + (v1.y - v2.y) * normal.y;
+    let impulse = -(1.0 + restitution) * closing.min(0.0) / total;
+    if closing < 0.0 {
+        let impulse = normal * impulse;
+        v1.x += impulse.x * inv1;
+        v1.y += impulse.y * inv1;
+        v2.x -= impulse.x * inv2;
+        v2.y -= impulse.y * inv2;
+    }
+    for v in [v1, v2] {
+        if v.x.abs() < COLLISION_THRESHOLD {
+            v.x = 0.0;
+        }
+        if v.y.abs() < COLLISION_THRESHOLD {
+            v.y = 0.0;
+        }
+    }
+    Some((normal, impulse))
 }
 
 pub fn physics_collide(
     mut q_movable: Query<
-        (&Hitbox, &mut Transform, &mut Velocity),
-        With<Moveable>,
+        (
+            Entity,
+            &Hitbox,
+            &mut Transform,
+            &mut Velocity,
+            &Moveable,
+            Option<&PrevPosition>,
+            Option<&DropThrough>,
+        ),
+        Without<Sensor>,
+    >,
+    q_immovable: Query<
+        (
+            Entity,
+            &Hitbox,
+            &Transform,
+            Option<&Velocity>,
+            Option<&PhysicsMaterial>,
+            Option<&OneWay>,
+        ),
+        (Without<Moveable>, Without<Sensor>),
     >,
-    q_immovable: Query<(&mut Hitbox, &Transform), Without<Moveable>>,
+    broadphase: Res<Broadphase>,
+    mut collisions: EventWriter<CollisionEvent>,
 ) {
+    let still = Velocity { x: 0.0, y: 0.0 };
+    let plain = PhysicsMaterial::default();
     // First resolve all collisions between two moveable objects
+    let mut movers = SpatialHash::default();
+    for (e, h, t, ..) in q_movable.iter() {
+        movers.insert_hitbox(e, h, t);
+    }
+    let entities: Vec<Entity> = q_movable.iter().map(|(e, ..)| e).collect();
+    for e1 in entities {
+        let candidates = {
+            let Ok((_, h1, t1, ..)) = q_movable.get(e1) else {
+                continue;
+            };
+            movers.query_hitbox(h1, t1)
+        };
+        // Only look at each pair once
+        for e2 in candidates.into_iter().filter(|e2| *e2 > e1) {
+            let Ok([first, second]) = q_movable.get_many_mut([e1, e2]) else {
+                continue;
+            };
+            let (_, h1, mut t1, mut v1, m1, p1, ..) = first;
+            let (_, h2, mut t2, mut v2, m2, p2, ..) = second;
+            // Frozen things, like dead agents, don't get in anyone's way
+            if !m1.gravity_enabled
+                || !m2.gravity_enabled
+                || !h1.layers.interacts(&h2.layers)
+                || !are_colliding(h1, &t1, h2, &t2)
+            {
+                continue;
+            }
+            let p1 = p1.map_or(t1.translation.truncate(), |p| p.0);
+            let p2 = p2.map_or(t2.translation.truncate(), |p| p.0);
+            let resolved = resolve_move_move_collision(
+                MovingBody {
+                    hitbox: h1,
+                    transform: &mut t1,
+                    velocity: &mut v1,
+                    moveable: m1,
+                    prev: p1,
+                },
+                MovingBody {
+                    hitbox: h2,
+                    transform: &mut t2,
+                    velocity: &mut v2,
+                    moveable: m2,
+                    prev: p2,
+                },
+            );
+            if let Some((normal, impulse)) = resolved {
+                collisions.send(CollisionEvent {
+                    a: e1,
+                    b: e2,
+                    normal,
+                    impulse,
+                });
+            }
+        }
+    }
     // Then resolve all collisions between moveable and immoveable
-    for (h1, mut t1, mut v1) in q_movable.iter_mut() {
-        for (h2, t2) in q_immovable.iter() {
-            if !are_colliding(h1, &t1, h2, t2) {
+    for (e1, h1, mut t1, mut v1, m1, prev, drop) in q_movable.iter_mut() {
+        // Without a previous position, go as if it didn't move
+        let prev = prev.map_or(t1.translation.truncate(), |p| p.0);
+        let dropping = drop.is_some_and(|d| d.0);
+        let candidates = broadphase.immovable.query_hitbox(h1, &t1);
+        for (e2, h2, t2, v2, material, one_way) in
+            q_immovable.iter_many(&candidates)
+        {
+            let one_way = one_way.is_some();
+            if !h1.layers.interacts(&h2.layers)
+                || (one_way && dropping)
+                || !are_colliding(h1, &t1, h2, t2)
+            {
                 continue;
             }
-            resolve_move_immove_collision(h1, &mut t1, &mut v1, h2, t2);
+            let resolved = resolve_move_immove_collision(
+                MovingBody {
+                    hitbox: h1,
+                    transform: &mut t1,
+                    velocity: &mut v1,
+                    moveable: m1,
+                    prev,
+                },
+                FixedBody {
+                    hitbox: h2,
+                    transform: t2,
+                    velocity: v2.unwrap_or(&still),
+                    material: material.unwrap_or(&plain),
+                    one_way,
+                },
+            );
+            if let Some((normal, impulse)) = resolved {
+                collisions.send(CollisionEvent {
+                    a: e1,
+                    b: e2,
+                    normal,
+                    impulse,
+                });
+            }
         }
     }
 }
 
 pub fn register_physics(app: &mut App) {
-    app.add_systems(Startup, physics_setup)
+    app.init_resource::<Broadphase>()
+        .add_systems(Startup, physics_setup)
         .add_systems(Update, physics_gravity)
-        .add_systems(Update, physics_move.after(physics_gravity))
+        .add_systems(Update, physics_kinematic.after(physics_gravity))
+        .add_systems(Update, physics_broadphase.after(physics_kinematic))
+        .add_systems(Update, physics_move.after(physics_broadphase))
         .add_systems(Update, physics_collide.after(physics_move));
+    register_physics_events(app);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI, SQRT_2};
+
+    use super::*;
+    use crate::environment::block::Surface;
+
+    fn rect(size: Vec2) -> Hitbox {
+        Hitbox { size, ..default() }
+    }
+
+    #[test]
+    fn fast_hitbox_stops_at_thin_block() {
+        let mover = rect(Vec2::splat(10.0));
+        let start = Transform::from_xyz(0.0, 100.0, 0.0);
+        let block = rect(Vec2::new(40.0, 2.0));
+        let block_at = Transform::from_xyz(0.0, 0.0, 0.0);
+        // Far enough in one frame to skip right over the block
+        let delta = Vec2::new(0.0, -500.0);
+
+        let (toi, normal) =
+            sweep(&mover, &start, delta, &block, &block_at).unwrap();
+        let stopped = Transform {
+            translation: start.translation + (delta * toi).extend(0.0),
+            ..start
+        };
+        let (_, _, _, bot) = get_bounds(&mover, &stopped);
+        let (_, _, top, _) = get_bounds(&block, &block_at);
+        assert_eq!(normal, Vec2::Y);
+        assert!((bot - top).abs() < 1e-3, "stopped at {bot}, not {top}");
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    fn assert_bounds(
+        hitbox: &Hitbox,
+        transform: &Transform,
+        expected: (f32, f32, f32, f32),
+    ) {
+        let bounds = get_bounds(hitbox, transform);
+        let (l, r, t, b) = bounds;
+        let (el, er, et, eb) = expected;
+        assert!(
+            close(l, el) && close(r, er) && close(t, et) && close(b, eb),
+            "bounds {bounds:?}, expected {expected:?}"
+        );
+    }
+
+    /// A wide block with its top at y = 0
+    fn floor() -> (Hitbox, Transform) {
+        (
+            rect(Vec2::new(100.0, 20.0)),
+            Transform::from_xyz(0.0, -10.0, 0.0),
+        )
+    }
+
+    fn assert_lands(hitbox: &Hitbox, transform: &Transform, depth: f32) {
+        let (h2, t2) = floor();
+        let contact = hitbox_contact(hitbox, transform, &h2, &t2).unwrap();
+        assert!(
+            contact.normal.abs_diff_eq(Vec2::Y, 1e-3)
+                && close(contact.depth, depth),
+            "contact {contact:?}, expected {depth} deep along y"
+        );
+    }
+
+    #[test]
+    fn offset_rect() {
+        let hitbox = Hitbox {
+            pos: Vec2::new(30.0, 0.0),
+            ..rect(Vec2::splat(20.0))
+        };
+        let transform = Transform::from_xyz(0.0, 9.0, 0.0);
+        assert_bounds(&hitbox, &transform, (20.0, 40.0, 19.0, -1.0));
+        assert_lands(&hitbox, &transform, 1.0);
+        // Off the end of the floor, even though the transform isn't
+        let past = Transform::from_xyz(40.0, 9.0, 0.0);
+        let (h2, t2) = floor();
+        assert!(hitbox_contact(&hitbox, &past, &h2, &t2).is_none());
+    }
+
+    #[test]
+    fn scaled_rect() {
+        let hitbox = Hitbox {
+            pos: Vec2::new(0.0, 2.0),
+            ..rect(Vec2::splat(10.0))
+        };
+        // Flipped upside down, so the offset ends up below
+        let transform = Transform::from_xyz(0.0, 12.0, 0.0)
+            .with_scale(Vec3::new(3.0, -2.0, 1.0));
+        assert_bounds(&hitbox, &transform, (-15.0, 15.0, 18.0, -2.0));
+        assert_lands(&hitbox, &transform, 2.0);
+    }
+
+    #[test]
+    fn rotated_rect() {
+        let hitbox = rect(Vec2::new(20.0, 10.0));
+        let turned = Transform::from_xyz(0.0, 9.0, 0.0)
+            .with_rotation(Quat::from_rotation_z(FRAC_PI_2));
+        assert_bounds(&hitbox, &turned, (-5.0, 5.0, 19.0, -1.0));
+        assert_lands(&hitbox, &turned, 1.0);
+
+        // Stood on a corner
+        let square = rect(Vec2::splat(10.0));
+        let corner = 5.0 * SQRT_2;
+        let diamond = Transform::from_xyz(0.0, corner - 0.5, 0.0)
+            .with_rotation(Quat::from_rotation_z(FRAC_PI_4));
+        assert_bounds(
+            &square,
+            &diamond,
+            (-corner, corner, 2.0 * corner - 0.5, -0.5),
+        );
+        assert_lands(&square, &diamond, 0.5);
+    }
+
+    #[test]
+    fn polygon() {
+        let hitbox = Hitbox {
+            pos: Vec2::new(5.0, 0.0),
+            ..Hitbox::polygon(vec![
+                Vec2::new(-10.0, 0.0),
+                Vec2::new(10.0, 0.0),
+                Vec2::new(0.0, 10.0),
+            ])
+        };
+        let transform = Transform::from_xyz(0.0, -1.0, 0.0)
+            .with_scale(Vec3::new(2.0, 1.0, 1.0));
+        assert_bounds(&hitbox, &transform, (-10.0, 30.0, 9.0, -1.0));
+        assert_lands(&hitbox, &transform, 1.0);
+
+        // Upside down, it only touches with its tip
+        let flipped = Transform::from_xyz(0.0, 9.5, 0.0)
+            .with_rotation(Quat::from_rotation_z(PI));
+        assert_bounds(&hitbox, &flipped, (-15.0, 5.0, 9.5, -0.5));
+        assert_lands(&hitbox, &flipped, 0.5);
+    }
+
+    #[test]
+    fn circle() {
+        let hitbox = Hitbox {
+            pos: Vec2::new(0.0, 5.0),
+            ..Hitbox::circle(5.0)
+        };
+        // Scaling grows the radius along with the offset
+        let transform =
+            Transform::from_xyz(0.0, -2.0, 0.0).with_scale(Vec3::splat(2.0));
+        assert_bounds(&hitbox, &transform, (-10.0, 10.0, 18.0, -2.0));
+        assert_lands(&hitbox, &transform, 2.0);
+
+        let other = Hitbox::circle(5.0);
+        let beside = Transform::from_xyz(-13.0, 8.0, 0.0);
+        let contact =
+            hitbox_contact(&other, &beside, &hitbox, &transform).unwrap();
+        assert!(contact.normal.abs_diff_eq(Vec2::NEG_X, 1e-3));
+        assert!(close(contact.depth, 2.0));
+    }
+
+    /// Moves a box that falls along `gravity_dir` a little way from `from`
+    /// along `heading`, into a one-way platform at the origin
+    fn hit_one_way(
+        gravity_dir: Dir,
+        from: Vec2,
+        heading: Vec2,
+    ) -> Option<(Vec2, f32)> {
+        let hitbox = rect(Vec2::splat(10.0));
+        let moveable = Moveable {
+            gravity_enabled: true,
+            gravity_scale: 1.0,
+            gravity_dir,
+            mass: 1.0,
+            restitution: 0.0,
+        };
+        let fall = heading * 4.0;
+        let mut transform = Transform::from_translation(from.extend(0.0));
+        transform.translation += fall.extend(0.0);
+        let mut velocity = Velocity {
+            x: fall.x * 60.0,
+            y: fall.y * 60.0,
+        };
+        let platform = rect(Vec2::new(40.0, 40.0));
+        resolve_move_immove_collision(
+            MovingBody {
+                hitbox: &hitbox,
+                transform: &mut transform,
+                velocity: &mut velocity,
+                moveable: &moveable,
+                prev: from,
+            },
+            FixedBody {
+                hitbox: &platform,
+                transform: &Transform::IDENTITY,
+                velocity: &Velocity { x: 0.0, y: 0.0 },
+                material: &PhysicsMaterial::default(),
+                one_way: true,
+            },
+        )
+    }
+
+    #[test]
+    fn one_way_platforms_follow_gravity() {
+        // Just past each face, falling into it
+        let above = Vec2::new(0.0, 25.0);
+        let below = Vec2::new(0.0, -25.0);
+        let right = Vec2::new(25.0, 0.0);
+        let (normal, _) = hit_one_way(Dir::Down, above, Vec2::NEG_Y).unwrap();
+        assert_eq!(normal, Vec2::Y);
+        let (normal, _) = hit_one_way(Dir::Up, below, Vec2::Y).unwrap();
+        assert_eq!(normal, Vec2::NEG_Y);
+        let (normal, _) = hit_one_way(Dir::Left, right, Vec2::NEG_X).unwrap();
+        assert_eq!(normal, Vec2::X);
+        // Jumping up into it from underneath, whichever way that is
+        assert!(hit_one_way(Dir::Down, below, Vec2::Y).is_none());
+        assert!(hit_one_way(Dir::Up, above, Vec2::NEG_Y).is_none());
+        assert!(hit_one_way(Dir::Down, right, Vec2::NEG_X).is_none());
+    }
+
+    #[test]
+    fn sweep_misses_block_out_of_the_way() {
+        let mover = rect(Vec2::splat(10.0));
+        let start = Transform::from_xyz(100.0, 100.0, 0.0);
+        let block = rect(Vec2::new(40.0, 2.0));
+        let block_at = Transform::from_xyz(0.0, 0.0, 0.0);
+        let delta = Vec2::new(0.0, -500.0);
+        assert!(sweep(&mover, &start, delta, &block, &block_at).is_none());
+    }
+    #[test]
+    fn slides_across_flush_blocks() {
+        let mover = rect(Vec2::splat(10.0));
+        let across = |right_top: f32| {
+            let left = rect(Vec2::new(50.0, 20.0));
+            let left_at = Transform::from_xyz(-25.0, -10.0, 0.0);
+            let right = rect(Vec2::new(50.0, 20.0));
+            let right_at = Transform::from_xyz(25.0, right_top - 10.0, 0.0);
+            // Resting on the left block, over the seam and a little down
+            let mut transform = Transform::from_xyz(-10.0, 5.0, 0.0);
+            sweep_move(
+                &mover,
+                &mut transform,
+                Vec2::new(20.0, -1.0),
+                &[(&left, &left_at, false), (&right, &right_at, false)],
+                Vec2::Y,
+                false,
+            );
+            transform.translation.truncate()
+        };
+        let level = across(0.0);
+        assert!(close(level.x, 10.0));
+        assert!(level.y > 5.0 - SWEEP_SKIN - 1e-3);
+        // A step up is a real edge, so it still stops there
+        let step = across(2.0);
+        assert!(step.x < 0.0);
+    }
+    /// How fast a box sliding along a floor of `surface` is still going
+    /// after half a second
+    fn slide(surface: Surface) -> f32 {
+        let hitbox = rect(Vec2::splat(10.0));
+        let moveable = Moveable {
+            gravity_enabled: true,
+            gravity_scale: 1.0,
+            gravity_dir: Dir::Down,
+            mass: 1.0,
+            restitution: 0.0,
+        };
+        let (floor, floor_at) = floor();
+        let material = surface.material();
+        let mut velocity = Velocity { x: 200.0, y: 0.0 };
+        for _ in 0..30 {
+            // Pulled a little into the floor, like gravity does every frame
+            let mut transform = Transform::from_xyz(0.0, 4.9, 0.0);
+            velocity.y = -10.0;
+            resolve_move_immove_collision(
+                MovingBody {
+                    hitbox: &hitbox,
+                    transform: &mut transform,
+                    velocity: &mut velocity,
+                    moveable: &moveable,
+                    prev: Vec2::new(0.0, 5.0),
+                },
+                FixedBody {
+                    hitbox: &floor,
+                    transform: &floor_at,
+                    velocity: &Velocity { x: 0.0, y: 0.0 },
+                    material: &material,
+                    one_way: false,
+                },
+            );
+        }
+        velocity.x
+    }
+
+    #[test]
+    fn slides_further_on_ice() {
+        let (ice, normal) = (slide(Surface::Ice), slide(Surface::Normal));
+        assert!(
+            ice > 100.0 && normal < 1.0,
+            "{ice} on ice, {normal} on normal"
+        );
+    }
 }